@@ -1,11 +1,24 @@
 pub const MIN_DURATION: u64 = 1;
 pub const CALC_PRECISION: u128 = (u64::MAX as u128) + 1;
 
-pub const MAX_MINT_LIMIT: usize = 300;
+pub const MULTIPLIER_BASE: u32 = 10_000;
+pub const MAX_REWARD_STREAMS: usize = 3;
+pub const MAX_LOCK_TIERS: usize = 4;
+pub const PENALTY_BASE: u16 = 10_000;
+pub const EMISSION_BASE: u32 = 10_000;
+pub const MAX_EMISSION_CHECKPOINTS: usize = 4;
 // pub const PUBKEY_SIZE: usize = 32;
 pub const VAULT_REWARD_SEED: &str = "x_token_vault_reward";
 pub const VAULT_USER_SEED: &str = "x_token_vault_user";
 pub const VAULT_STAKE_SEED: &str = "x_token_vault_stake";
+pub const VAULT_WHITELIST_SEED: &str = "x_token_vault_whitelist";
+pub const VAULT_RECEIPT_SEED: &str = "x_token_vault_receipt";
+pub const VAULT_ESCROW_SEED: &str = "x_token_vault_escrow";
+pub const VAULT_WEIGHT_SEED: &str = "x_token_vault_weight";
+pub const VAULT_FREEZE_SEED: &str = "x_token_vault_freeze";
+pub const VAULT_ROLE_SEED: &str = "x_token_vault_role";
+pub const VAULT_CONTRIBUTION_SEED: &str = "x_token_vault_contribution";
 pub const VAULT_CODE_SEED: &str = "x_token_value_code_seed";
 pub const VAULT_TOKEN_OFFER: &str = "x_token_count_seed";
 pub const VAULT_SEED: &str = "Value_Reward";
+pub const METADATA_SEED: &str = "metadata";
@@ -0,0 +1,279 @@
+use crate::constant::{VAULT_FREEZE_SEED, VAULT_STAKE_SEED};
+use crate::state::{CustodyMode, ErrorCode, Vault};
+use anchor_lang::{prelude::*, solana_program::program::invoke};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{Mint, TokenAccount};
+use spl_token::instruction::AuthorityType::AccountOwner;
+use std::slice::Iter;
+
+// accounts moving a staked token in and out of custody
+pub struct CustodyAccounts<'a, 'info> {
+    // token program
+    pub token_program: &'a AccountInfo<'info>,
+    // associated token account of the vault pda for the staked mint, only used by escrow custody,
+    // the client creates it before staking
+    pub escrow_account: Option<&'a AccountInfo<'info>>,
+    // mint of the staked token, only used by freeze custody
+    pub stake_mint: Option<&'a AccountInfo<'info>>,
+    // vault pda holding the freeze authority of the staked mint, only used by freeze custody
+    pub freeze_authority: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> CustodyAccounts<'a, 'info> {
+    // the custody account of a token follows its stake receipt in remaining accounts,
+    // the escrow token account for escrow custody and the stake mint for freeze custody
+    pub fn next(
+        vault: &Vault,
+        token_program: &'a AccountInfo<'info>,
+        freeze_authority: &'a AccountInfo<'info>,
+        accounts: &mut Iter<'a, AccountInfo<'info>>,
+    ) -> std::result::Result<Self, ProgramError> {
+        let mut custody = CustodyAccounts {
+            token_program,
+            escrow_account: None,
+            stake_mint: None,
+            freeze_authority: Some(freeze_authority),
+        };
+        match vault.custody_mode {
+            CustodyMode::SetAuthority => {}
+            CustodyMode::Escrow => {
+                custody.escrow_account =
+                    Some(accounts.next().ok_or(ErrorCode::InvalidEscrowAccount)?);
+            }
+            CustodyMode::Freeze => {
+                custody.stake_mint =
+                    Some(accounts.next().ok_or(ErrorCode::InvalidFreezeAuthority)?);
+            }
+        }
+        Ok(custody)
+    }
+
+    fn escrow_account(
+        &self,
+        vault_pda: &Pubkey,
+        mint: &Pubkey,
+    ) -> std::result::Result<&'a AccountInfo<'info>, ProgramError> {
+        let escrow_account = self.escrow_account.ok_or(ErrorCode::InvalidEscrowAccount)?;
+        if *escrow_account.key != get_associated_token_address(vault_pda, mint) {
+            return Err(ErrorCode::InvalidEscrowAccount.into());
+        }
+        Ok(escrow_account)
+    }
+
+    // the freeze authority of the staked mint must have been handed to the vault freeze pda
+    fn freeze_accounts(
+        &self,
+        program_id: &Pubkey,
+        vault: &Pubkey,
+        mint: &Pubkey,
+    ) -> std::result::Result<(&'a AccountInfo<'info>, &'a AccountInfo<'info>, u8), ProgramError>
+    {
+        let stake_mint = self.stake_mint.ok_or(ErrorCode::InvalidFreezeAuthority)?;
+        let freeze_authority = self
+            .freeze_authority
+            .ok_or(ErrorCode::InvalidFreezeAuthority)?;
+        let (freeze_address, freeze_bump) = Pubkey::find_program_address(
+            &[VAULT_FREEZE_SEED.as_bytes(), vault.as_ref()],
+            program_id,
+        );
+        if *stake_mint.key != *mint || *freeze_authority.key != freeze_address {
+            return Err(ErrorCode::InvalidFreezeAuthority.into());
+        }
+
+        let mint_data = Account::<Mint>::try_from(stake_mint)?;
+        if mint_data.freeze_authority != Some(freeze_address).into() {
+            return Err(ErrorCode::InvalidFreezeAuthority.into());
+        }
+        Ok((stake_mint, freeze_authority, freeze_bump))
+    }
+}
+
+// hands the staked token over to the vault pda of the staker
+pub fn take_custody<'info>(
+    program_id: &Pubkey,
+    vault: &Account<'info, Vault>,
+    staker: &AccountInfo<'info>,
+    vault_pda: &AccountInfo<'info>,
+    stake_account: &Account<'info, TokenAccount>,
+    custody: &CustodyAccounts<'_, 'info>,
+) -> ProgramResult {
+    let (vault_pda_address, _vault_bump) = Pubkey::find_program_address(
+        &[
+            VAULT_STAKE_SEED.as_bytes(),
+            vault.key().as_ref(),
+            staker.key.as_ref(),
+        ],
+        program_id,
+    );
+    if *vault_pda.key != vault_pda_address {
+        return Err(ErrorCode::InvalidStakeAccount.into());
+    }
+
+    match vault.custody_mode {
+        CustodyMode::SetAuthority => {
+            // transfer token authority
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::SetAuthority {
+                    current_authority: staker.clone(),
+                    account_or_mint: stake_account.to_account_info(),
+                },
+            );
+
+            anchor_spl::token::set_authority(cpi_context, AccountOwner, Some(vault_pda_address))
+        }
+        CustodyMode::Escrow => {
+            // transfer token into the escrow, the staker keeps owning the emptied account
+            let escrow_account = custody.escrow_account(vault_pda.key, &stake_account.mint)?;
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::Transfer {
+                    from: stake_account.to_account_info(),
+                    to: escrow_account.clone(),
+                    authority: staker.clone(),
+                },
+            );
+
+            anchor_spl::token::transfer(cpi_context, stake_account.amount)
+        }
+        CustodyMode::Freeze => {
+            // delegate the token to the vault pda and freeze it in the wallet of the staker
+            let vault_address = vault.key();
+            let (stake_mint, freeze_authority, freeze_bump) =
+                custody.freeze_accounts(program_id, &vault_address, &stake_account.mint)?;
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::Approve {
+                    to: stake_account.to_account_info(),
+                    delegate: vault_pda.clone(),
+                    authority: staker.clone(),
+                },
+            );
+
+            anchor_spl::token::approve(cpi_context, stake_account.amount)?;
+
+            let seeds = &[
+                VAULT_FREEZE_SEED.as_bytes(),
+                vault_address.as_ref(),
+                &[freeze_bump],
+            ];
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::FreezeAccount {
+                    account: stake_account.to_account_info(),
+                    mint: stake_mint.clone(),
+                    authority: freeze_authority.clone(),
+                },
+            );
+
+            anchor_spl::token::freeze_account(cpi_context.with_signer(&[&seeds[..]]))
+        }
+    }
+}
+
+// gives the staked amount of the token back to the staker
+#[allow(clippy::too_many_arguments)]
+pub fn release_custody<'info>(
+    program_id: &Pubkey,
+    vault: &Account<'info, Vault>,
+    staker: &AccountInfo<'info>,
+    vault_pda: &AccountInfo<'info>,
+    vault_stake_bump: u8,
+    stake_account: &Account<'info, TokenAccount>,
+    amount: u64,
+    custody: &CustodyAccounts<'_, 'info>,
+) -> ProgramResult {
+    let vault_address = vault.key();
+    let seeds = &[
+        VAULT_STAKE_SEED.as_bytes(),
+        vault_address.as_ref(),
+        staker.key.as_ref(),
+        &[vault_stake_bump],
+    ]; // need this to sign the pda, match the authority
+
+    match vault.custody_mode {
+        CustodyMode::SetAuthority => {
+            // transfer token authority
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::SetAuthority {
+                    current_authority: vault_pda.clone(),
+                    account_or_mint: stake_account.to_account_info(),
+                },
+            );
+
+            anchor_spl::token::set_authority(
+                cpi_context.with_signer(&[&seeds[..]]),
+                AccountOwner,
+                Some(*staker.key),
+            )
+        }
+        CustodyMode::Escrow => {
+            // every staked account of the mint shares the escrow, only the staked amount goes back
+            // and the escrow is closed once empty, its rent goes to the staker
+            let escrow_account = custody.escrow_account(vault_pda.key, &stake_account.mint)?;
+            let escrowed = Account::<TokenAccount>::try_from(escrow_account)?.amount;
+            let remaining = escrowed
+                .checked_sub(amount)
+                .ok_or(ErrorCode::InvalidEscrowAccount)?;
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::Transfer {
+                    from: escrow_account.clone(),
+                    to: stake_account.to_account_info(),
+                    authority: vault_pda.clone(),
+                },
+            );
+
+            anchor_spl::token::transfer(cpi_context.with_signer(&[&seeds[..]]), amount)?;
+            if remaining > 0 {
+                return Ok(());
+            }
+
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::CloseAccount {
+                    account: escrow_account.clone(),
+                    destination: staker.clone(),
+                    authority: vault_pda.clone(),
+                },
+            );
+
+            anchor_spl::token::close_account(cpi_context.with_signer(&[&seeds[..]]))
+        }
+        CustodyMode::Freeze => {
+            // thaw the token, then the staker revokes the vault pda delegation
+            let (stake_mint, freeze_authority, freeze_bump) =
+                custody.freeze_accounts(program_id, &vault_address, &stake_account.mint)?;
+            let freeze_seeds = &[
+                VAULT_FREEZE_SEED.as_bytes(),
+                vault_address.as_ref(),
+                &[freeze_bump],
+            ];
+            let cpi_context = CpiContext::new(
+                custody.token_program.clone(),
+                anchor_spl::token::ThawAccount {
+                    account: stake_account.to_account_info(),
+                    mint: stake_mint.clone(),
+                    authority: freeze_authority.clone(),
+                },
+            );
+
+            anchor_spl::token::thaw_account(cpi_context.with_signer(&[&freeze_seeds[..]]))?;
+
+            invoke(
+                &spl_token::instruction::revoke(
+                    custody.token_program.key,
+                    &stake_account.key(),
+                    staker.key,
+                    &[],
+                )?,
+                &[
+                    stake_account.to_account_info(),
+                    staker.clone(),
+                    custody.token_program.clone(),
+                ],
+            )
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::constant::MAX_REWARD_STREAMS;
+use crate::state::{CustodyMode, EmissionSchedule, LockTier, Role, StakeMode, WhitelistType};
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct VaultCreated {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_duration: u64,
+    pub stake_token_count: u32,
+    pub stake_mode: StakeMode,
+    pub stake_mint: Pubkey,
+    pub custody_mode: CustodyMode,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RewardStreamAdded {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_duration: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RoleGranted {
+    pub vault: Pubkey,
+    pub holder: Pubkey,
+    pub role: Role,
+    pub reward_mint: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RoleRevoked {
+    pub vault: Pubkey,
+    pub holder: Pubkey,
+    pub role: Role,
+    pub reward_mint: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct WhitelistAdded {
+    pub vault: Pubkey,
+    pub address: Pubkey,
+    pub whitelist_type: WhitelistType,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct WhitelistRemoved {
+    pub vault: Pubkey,
+    pub address: Pubkey,
+    pub whitelist_type: WhitelistType,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct VaultPaused {
+    pub vault: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct VaultResumed {
+    pub vault: Pubkey,
+    pub paused_duration: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct VaultConfigUpdated {
+    pub vault: Pubkey,
+    pub reward_index: u8,
+    pub old_reward_duration: u64,
+    pub new_reward_duration: u64,
+    pub old_stake_token_count: u32,
+    pub new_stake_token_count: u32,
+    pub old_max_stake_per_user: u32,
+    pub new_max_stake_per_user: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MintWeightSet {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub weight: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MintWeightRemoved {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct LockTiersUpdated {
+    pub vault: Pubkey,
+    pub lock_tiers: Vec<LockTier>,
+    pub early_unstake_penalty: u16,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub vault: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CollectionSet {
+    pub vault: Pubkey,
+    pub collection: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EmissionScheduleSet {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub schedule: EmissionSchedule,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Funded {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub contribution: u64,
+    pub reward_rate: u128,
+    pub reward_duration_deadline: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UnallocatedWithdrawn {
+    pub vault: Pubkey,
+    pub reward_mint: Pubkey,
+    pub funder: Pubkey,
+    pub contribution: u64,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserCreated {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Staked {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub staker: Pubkey,
+    pub mint: Pubkey,
+    pub stake_account: Pubkey,
+    pub user_staked_count: u32,
+    pub vault_staked_count: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Unstaked {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub staker: Pubkey,
+    pub mint: Pubkey,
+    pub stake_account: Pubkey,
+    pub user_staked_count: u32,
+    pub vault_staked_count: u32,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Deposited {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub user_staked_weight: u64,
+    pub vault_staked_weight: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub staker: Pubkey,
+    pub amount: u64,
+    pub user_staked_weight: u64,
+    pub vault_staked_weight: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EarlyUnstakePenalized {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub stake_account: Pubkey,
+    // forfeited rewards, in the order of the vault reward streams
+    pub penalties: [u64; MAX_REWARD_STREAMS],
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct EmergencyUnstaked {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub staker: Pubkey,
+    pub mint: Pubkey,
+    pub stake_account: Pubkey,
+    // forfeited rewards, in the order of the vault reward streams
+    pub forfeited_rewards: [u64; MAX_REWARD_STREAMS],
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Compounded {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub staker: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+    pub user_staked_weight: u64,
+    pub vault_staked_weight: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Claimed {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub claimer: Pubkey,
+    pub recipient: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct RewardRecipientSet {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct UserClosed {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct VaultClosed {
+    pub vault: Pubkey,
+    pub refundee: Pubkey,
+    // refunded amounts, in the order of the vault reward streams
+    pub amounts: [u64; MAX_REWARD_STREAMS],
+    pub timestamp: u64,
+}
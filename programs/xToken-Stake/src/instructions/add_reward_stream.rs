@@ -0,0 +1,101 @@
+use crate::constant::{MAX_REWARD_STREAMS, MIN_DURATION, VAULT_REWARD_SEED};
+use crate::events::RewardStreamAdded;
+use crate::state::{ErrorCode, RewardStream, Role, Vault, VaultStatus};
+use crate::util::{get_now_timestamp, verify_role};
+
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{create, AssociatedToken, Create};
+use anchor_spl::token::Token;
+
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    // the vault authority or an admin, whose role entry is the first remaining account
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(mut,
+    constraint = vault.status == VaultStatus::Initialized)]
+    vault: Account<'info, Vault>,
+
+    // reward pda account
+    #[account(seeds = [VAULT_REWARD_SEED.as_bytes(), vault.key().as_ref()], bump = vault.reward_bump)]
+    reward: SystemAccount<'info>,
+
+    // reward token of the new stream
+    reward_mint: AccountInfo<'info>,
+
+    // reward token account to be created, owned by vault
+    #[account(mut)]
+    reward_account: UncheckedAccount<'info>,
+
+    rent: Sysvar<'info, Rent>,
+
+    #[account(address = anchor_spl::associated_token::ID)]
+    associated_token: Program<'info, AssociatedToken>,
+
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+
+    system_program: Program<'info, System>,
+}
+
+pub fn add_reward_stream(ctx: Context<AddRewardStream>, reward_duration: u64) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    // check reward_duration
+    if reward_duration < MIN_DURATION {
+        return Err(ErrorCode::DurationTooShort.into());
+    }
+
+    let vault = &mut ctx.accounts.vault;
+    let count = vault.reward_stream_count as usize;
+    if count >= MAX_REWARD_STREAMS {
+        return Err(ErrorCode::RewardStreamsFull.into());
+    }
+
+    let reward_mint = ctx.accounts.reward_mint.key();
+    if vault.reward_streams[..count]
+        .iter()
+        .any(|stream| stream.reward_mint == reward_mint)
+    {
+        return Err(ErrorCode::RewardStreamExists.into());
+    }
+
+    // create reward_account
+    let cpi_context = Create {
+        payer: ctx.accounts.authority.to_account_info(),
+        associated_token: ctx.accounts.reward_account.to_account_info(),
+        authority: ctx.accounts.reward.to_account_info(),
+        mint: ctx.accounts.reward_mint.to_account_info(),
+        rent: ctx.accounts.rent.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+    };
+    let create_ctx = CpiContext::new(ctx.accounts.associated_token.to_account_info(), cpi_context);
+    create(create_ctx)?;
+
+    vault.reward_streams[count] = RewardStream {
+        reward_mint,
+        reward_mint_account: ctx.accounts.reward_account.key(),
+        reward_duration,
+        ..RewardStream::default()
+    };
+    vault.reward_stream_count = vault
+        .reward_stream_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(RewardStreamAdded {
+        vault: vault.key(),
+        reward_mint,
+        reward_duration,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
-use crate::constant::VAULT_REWARD_SEED;
+use crate::constant::{CALC_PRECISION, VAULT_REWARD_SEED};
+use crate::events::{Claimed, RewardRecipientSet};
 use crate::state::{ErrorCode, User, Vault, VaultStatus};
-use crate::util::update_rewards;
+use crate::util::{get_now_timestamp, update_rewards};
 
 use anchor_lang::prelude::*;
-use anchor_spl::associated_token::{create, AssociatedToken, Create};
+use anchor_spl::associated_token::{create, get_associated_token_address, AssociatedToken, Create};
 use anchor_spl::token::{Token, TokenAccount};
+use std::ops::Range;
 
 #[derive(Accounts)]
 pub struct Claim<'info> {
@@ -15,9 +17,7 @@ pub struct Claim<'info> {
     // vault
     #[account(mut,
     has_one = authority,
-    has_one = reward_mint_account,
-    has_one = reward_mint,
-    constraint = vault.status == VaultStatus::Initialized)]
+    constraint = vault.status == VaultStatus::Initialized || vault.status == VaultStatus::Paused)]
     vault: Account<'info, Vault>,
 
     // vault owner
@@ -28,24 +28,53 @@ pub struct Claim<'info> {
     seeds = [VAULT_REWARD_SEED.as_bytes(), vault.to_account_info().key.as_ref()], bump = vault.reward_bump)]
     reward: AccountInfo<'info>,
 
-    // reward mint
-    reward_mint: AccountInfo<'info>,
-
-    // vault reward account
+    // user
     #[account(mut,
-    constraint = reward_mint_account.mint == reward_mint.key())]
-    reward_mint_account: Box<Account<'info, TokenAccount>>,
+    constraint = user.vault == *vault.to_account_info().key,
+    constraint = user.key == *claimer.key)]
+    user: Account<'info, User>,
+
+    // associated token program
+    #[account(address = anchor_spl::associated_token::ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
+
+    // rent
+    rent: Sysvar<'info, Rent>,
+
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+
+    // system program
+    system_program: Program<'info, System>,
+}
 
-    // claimer reward account
+#[derive(Accounts)]
+pub struct CrankClaim<'info> {
+    // caller, pays the rent of missing recipient reward accounts
     #[account(mut)]
-    reward_account: AccountInfo<'info>,
+    caller: Signer<'info>,
+
+    // vault
+    #[account(mut,
+    constraint = vault.status == VaultStatus::Initialized || vault.status == VaultStatus::Paused)]
+    vault: Account<'info, Vault>,
+
+    // reward pda account
+    #[account(mut,
+    seeds = [VAULT_REWARD_SEED.as_bytes(), vault.to_account_info().key.as_ref()], bump = vault.reward_bump)]
+    reward: AccountInfo<'info>,
 
     // user
     #[account(mut,
     constraint = user.vault == *vault.to_account_info().key,
-    constraint = user.key == *claimer.key)]
+    constraint = user.reward_recipient != Pubkey::default() @ ErrorCode::NoRewardRecipient,
+    constraint = user.reward_recipient == *recipient.key @ ErrorCode::NoRewardRecipient)]
     user: Account<'info, User>,
 
+    // reward recipient set by the user
+    recipient: AccountInfo<'info>,
+
     // associated token program
     #[account(address = anchor_spl::associated_token::ID)]
     associated_token_program: Program<'info, AssociatedToken>,
@@ -61,73 +90,213 @@ pub struct Claim<'info> {
     system_program: Program<'info, System>,
 }
 
-pub fn claim(ctx: Context<Claim>) -> ProgramResult {
-    let vault = &mut ctx.accounts.vault;
-    if vault.status != VaultStatus::Initialized {
-        return Err(ErrorCode::VaultNotReady.into());
+#[derive(Accounts)]
+pub struct SetRewardRecipient<'info> {
+    // user authority
+    authority: Signer<'info>,
+
+    // user
+    #[account(mut,
+    constraint = user.key == *authority.key)]
+    user: Account<'info, User>,
+}
+
+// accounts paying out pending rewards
+struct RewardPayout<'a, 'info> {
+    // pays the rent of missing reward accounts
+    payer: &'a AccountInfo<'info>,
+    // owner of the reward accounts the rewards are paid to
+    recipient: &'a AccountInfo<'info>,
+    // reward pda
+    reward: &'a AccountInfo<'info>,
+    // rent
+    rent: &'a AccountInfo<'info>,
+    // associated token program
+    associated_token_program: &'a AccountInfo<'info>,
+    // token program
+    token_program: &'a AccountInfo<'info>,
+    // system program
+    system_program: &'a AccountInfo<'info>,
+}
+
+fn claimed_indexes(
+    vault: &Vault,
+    reward_index: Option<u8>,
+) -> std::result::Result<Range<u8>, ProgramError> {
+    match reward_index {
+        Some(index) if index < vault.reward_stream_count => Ok(index..index + 1),
+        Some(_) => Err(ErrorCode::RewardStreamDoesNotExist.into()),
+        None => Ok(0..vault.reward_stream_count),
     }
+}
 
-    let user = &mut ctx.accounts.user;
-    update_rewards(vault, user).unwrap();
+// pays the pending rewards of the streams to the associated token accounts of the recipient,
+// remaining accounts hold the reward mint, the vault reward account and the recipient reward account
+// of every paid stream, rewards must be updated first
+fn pay_rewards<'info>(
+    payout: &RewardPayout<'_, 'info>,
+    vault: &mut Account<'info, Vault>,
+    user: &mut Account<'info, User>,
+    indexes: Range<u8>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> ProgramResult {
+    // rewards of locked stakes are held back so they can still be penalized by an early unstake
+    if get_now_timestamp()? < user.lock_end_time {
+        return Err(ErrorCode::RewardsLocked.into());
+    }
+
+    let vault_address = vault.key();
+    let seeds = &[
+        VAULT_REWARD_SEED.as_bytes(),
+        vault_address.as_ref(),
+        &[vault.reward_bump],
+    ];
+
+    let accounts = &mut remaining_accounts.iter();
+    for index in indexes.map(|index| index as usize) {
+        let reward_mint = accounts.next().ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+        let reward_mint_account_info =
+            accounts.next().ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+        let reward_account = accounts.next().ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+
+        let stream = &mut vault.reward_streams[index];
+        if stream.reward_mint != *reward_mint.key
+            || stream.reward_mint_account != *reward_mint_account_info.key
+        {
+            return Err(ErrorCode::RewardStreamDoesNotExist.into());
+        }
+        if *reward_account.key
+            != get_associated_token_address(payout.recipient.key, reward_mint.key)
+        {
+            return Err(ErrorCode::InvalidRewardAccount.into());
+        }
+        let reward_mint_account = Account::<TokenAccount>::try_from(reward_mint_account_info)?;
 
-    if user.reward_earned_pending > 0 {
-        let mut reward_amount = user.reward_earned_pending;
-        let vault_current_amount = ctx.accounts.reward_mint_account.amount;
+        let reward = &mut user.rewards[index];
+        let reward_amount = reward.reward_earned_pending;
+        if reward_amount == 0 {
+            continue;
+        }
 
-        if vault_current_amount < reward_amount {
-            reward_amount = vault_current_amount;
+        // liability tracking guarantees the reward account covers every pending reward
+        if reward_mint_account.amount < reward_amount {
+            return Err(ErrorCode::InsufficientRewardFunds.into());
         }
 
-        if reward_amount > 0 {
-            // check claimer token account exists
-            if ctx.accounts.reward_account.owner == &System::id() {
-                let cpi_context = Create {
-                    payer: ctx.accounts.claimer.to_account_info(),
-                    associated_token: ctx.accounts.reward_account.to_account_info(),
-                    authority: ctx.accounts.claimer.to_account_info(),
-                    mint: ctx.accounts.reward_mint.clone(),
-                    rent: ctx.accounts.rent.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info(),
-                    system_program: ctx.accounts.system_program.to_account_info(),
-                };
-                let create_ctx = CpiContext::new(
-                    ctx.accounts.associated_token_program.to_account_info(),
-                    cpi_context,
-                );
-                create(create_ctx)?;
-            }
-
-            let vault_address = vault.key().clone();
-            let (_vault_pda, vault_bump) = Pubkey::find_program_address(
-                &[VAULT_REWARD_SEED.as_bytes(), vault_address.as_ref()],
-                ctx.program_id,
-            );
-            let seeds = &[
-                VAULT_REWARD_SEED.as_bytes(),
-                vault_address.as_ref(),
-                &[vault_bump],
-            ];
-
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info().clone(),
-                anchor_spl::token::Transfer {
-                    from: ctx.accounts.reward_mint_account.to_account_info().clone(),
-                    to: ctx.accounts.reward_account.to_account_info().clone(),
-                    authority: ctx.accounts.reward.to_account_info().clone(),
-                },
-            );
-            anchor_spl::token::transfer(cpi_ctx.with_signer(&[&seeds[..]]), reward_amount)?;
-
-            // update reward claiming vaules
-            user.reward_earned_pending = user
-                .reward_earned_pending
-                .checked_sub(reward_amount)
-                .unwrap();
-            user.reward_earned_claimed = user
-                .reward_earned_claimed
-                .checked_add(reward_amount)
-                .unwrap();
+        // check recipient token account exists
+        if reward_account.owner == &System::id() {
+            let cpi_context = Create {
+                payer: payout.payer.clone(),
+                associated_token: reward_account.clone(),
+                authority: payout.recipient.clone(),
+                mint: reward_mint.clone(),
+                rent: payout.rent.clone(),
+                token_program: payout.token_program.clone(),
+                system_program: payout.system_program.clone(),
+            };
+            let create_ctx = CpiContext::new(payout.associated_token_program.clone(), cpi_context);
+            create(create_ctx)?;
         }
+
+        let cpi_ctx = CpiContext::new(
+            payout.token_program.clone(),
+            anchor_spl::token::Transfer {
+                from: reward_mint_account_info.clone(),
+                to: reward_account.clone(),
+                authority: payout.reward.clone(),
+            },
+        );
+        anchor_spl::token::transfer(cpi_ctx.with_signer(&[&seeds[..]]), reward_amount)?;
+
+        // update reward claiming vaules
+        reward.reward_earned_pending = 0;
+        reward.reward_earned_claimed = reward
+            .reward_earned_claimed
+            .checked_add(reward_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        stream.reward_liability = stream
+            .reward_liability
+            .checked_sub(
+                (reward_amount as u128)
+                    .checked_mul(CALC_PRECISION)
+                    .ok_or(ErrorCode::MathOverflow)?,
+            )
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(Claimed {
+            vault: vault_address,
+            user: user.key(),
+            claimer: payout.payer.key(),
+            recipient: payout.recipient.key(),
+            reward_mint: stream.reward_mint,
+            amount: reward_amount,
+            timestamp: get_now_timestamp()?,
+        });
     }
     Ok(())
 }
+
+// remaining accounts hold the reward mint, the vault reward account and the claimer reward account
+// of every claimed stream, for all streams in order or only for the selected one
+pub fn claim<'info>(
+    ctx: Context<'_, '_, '_, 'info, Claim<'info>>,
+    reward_index: Option<u8>,
+) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Initialized && vault.status != VaultStatus::Paused {
+        return Err(ErrorCode::VaultNotReady.into());
+    }
+
+    let user = &mut ctx.accounts.user;
+    update_rewards(vault, user)?;
+
+    let indexes = claimed_indexes(vault, reward_index)?;
+    let payout = RewardPayout {
+        payer: &ctx.accounts.claimer.to_account_info(),
+        recipient: &ctx.accounts.claimer.to_account_info(),
+        reward: &ctx.accounts.reward,
+        rent: &ctx.accounts.rent.to_account_info(),
+        associated_token_program: &ctx.accounts.associated_token_program.to_account_info(),
+        token_program: &ctx.accounts.token_program.to_account_info(),
+        system_program: &ctx.accounts.system_program.to_account_info(),
+    };
+    pay_rewards(&payout, vault, user, indexes, ctx.remaining_accounts)
+}
+
+// anyone can push the pending rewards of a user to the recipient the user set, remaining accounts
+// are laid out as for claim with the recipient reward accounts
+pub fn crank_claim<'info>(
+    ctx: Context<'_, '_, '_, 'info, CrankClaim<'info>>,
+    reward_index: Option<u8>,
+) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    let user = &mut ctx.accounts.user;
+    update_rewards(vault, user)?;
+
+    let indexes = claimed_indexes(vault, reward_index)?;
+    let payout = RewardPayout {
+        payer: &ctx.accounts.caller.to_account_info(),
+        recipient: &ctx.accounts.recipient,
+        reward: &ctx.accounts.reward,
+        rent: &ctx.accounts.rent.to_account_info(),
+        associated_token_program: &ctx.accounts.associated_token_program.to_account_info(),
+        token_program: &ctx.accounts.token_program.to_account_info(),
+        system_program: &ctx.accounts.system_program.to_account_info(),
+    };
+    pay_rewards(&payout, vault, user, indexes, ctx.remaining_accounts)
+}
+
+// the default key clears the recipient, rewards are then only claimed by the user
+pub fn set_reward_recipient(ctx: Context<SetRewardRecipient>, recipient: Pubkey) -> ProgramResult {
+    let user = &mut ctx.accounts.user;
+    user.reward_recipient = recipient;
+
+    emit!(RewardRecipientSet {
+        vault: user.vault,
+        user: user.key(),
+        recipient,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
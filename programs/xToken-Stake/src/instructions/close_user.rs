@@ -1,6 +1,7 @@
 use crate::{
+    events::UserClosed,
     state::{ErrorCode, User, Vault, VaultStatus},
-    util::update_rewards,
+    util::{get_now_timestamp, update_rewards},
 };
 use anchor_lang::prelude::*;
 
@@ -10,10 +11,12 @@ pub struct CloseUser<'info> {
     authority: Signer<'info>,
 
     #[account(mut,
-    constraint = vault.status == VaultStatus::Initialized)]
+    constraint = vault.status == VaultStatus::Initialized || vault.status == VaultStatus::Paused)]
     vault: Account<'info, Vault>,
 
+    // user account is closed and its rent returned, so the same seeds can be registered again
     #[account(mut,
+    close = authority,
     constraint = user.key == *authority.key,
     constraint = user.vault == *vault.to_account_info().key)]
     user: Account<'info, User>,
@@ -21,22 +24,36 @@ pub struct CloseUser<'info> {
 
 pub fn close_user(ctx: Context<CloseUser>) -> ProgramResult {
     let vault = &mut ctx.accounts.vault;
-    if vault.status != VaultStatus::Initialized {
+    if vault.status != VaultStatus::Initialized && vault.status != VaultStatus::Paused {
         return Err(ErrorCode::VaultNotReady.into());
     }
 
     let user = &mut ctx.accounts.user;
-    if user.mint_staked_count > 0 {
+    if user.mint_staked_count > 0 || user.staked_weight > 0 {
         return Err(ErrorCode::StakeExist.into());
     }
 
-    if user.reward_earned_pending > 0 {
+    update_rewards(vault, user)?;
+
+    if user
+        .rewards
+        .iter()
+        .any(|reward| reward.reward_earned_pending > 0)
+    {
         return Err(ErrorCode::EarnedPendingExist.into());
     }
 
-    vault.user_count = vault.user_count.checked_sub(1).unwrap();
-
-    update_rewards(vault, user).unwrap();
+    vault.user_count = vault
+        .user_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(UserClosed {
+        vault: vault.key(),
+        user: user.key(),
+        authority: ctx.accounts.authority.key(),
+        timestamp: get_now_timestamp()?,
+    });
 
     Ok(())
 }
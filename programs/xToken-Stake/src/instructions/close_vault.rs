@@ -1,5 +1,6 @@
-use crate::constant::VAULT_REWARD_SEED;
-use crate::state::{Vault, VaultStatus};
+use crate::constant::{MAX_REWARD_STREAMS, VAULT_REWARD_SEED};
+use crate::events::VaultClosed;
+use crate::state::{ErrorCode, Vault, VaultStatus};
 use crate::util::get_now_timestamp;
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::{create, AssociatedToken, Create};
@@ -15,13 +16,10 @@ pub struct CloseVault<'info> {
     #[account(mut,
     close = refundee,
     has_one = authority,
-    has_one = reward_mint_account,
-    has_one = reward_mint,
     constraint = vault.status == VaultStatus::Initialized,
     constraint = vault.user_count == 0,
-    constraint = vault.reward_duration_deadline > 0,
-    constraint = vault.reward_duration_deadline < get_now_timestamp(),
     constraint = vault.staked_count == 0,
+    constraint = vault.staked_weight == 0,
     )]
     vault: Account<'info, Vault>,
 
@@ -30,18 +28,9 @@ pub struct CloseVault<'info> {
         seeds = [VAULT_REWARD_SEED.as_bytes(), vault.to_account_info().key.as_ref()], bump = vault.reward_bump)]
     reward: SystemAccount<'info>,
 
-    // reward mint token
-    reward_mint: AccountInfo<'info>,
-
     #[account(mut)]
     refundee: AccountInfo<'info>,
 
-    #[account(mut)]
-    refund_account: AccountInfo<'info>,
-
-    #[account(mut)]
-    reward_mint_account: Box<Account<'info, TokenAccount>>,
-
     // associated token program
     #[account(address = anchor_spl::associated_token::ID)]
     associated_token_program: Program<'info, AssociatedToken>,
@@ -57,8 +46,30 @@ pub struct CloseVault<'info> {
     system_program: Program<'info, System>,
 }
 
-pub fn close_vault(ctx: Context<CloseVault>) -> ProgramResult {
+// remaining accounts hold the reward mint, the vault reward account and the refund account
+// of every reward stream in order
+pub fn close_vault<'info>(ctx: Context<'_, '_, '_, 'info, CloseVault<'info>>) -> ProgramResult {
     let vault = &mut ctx.accounts.vault;
+    // the period of every funded reward stream must be over, a never funded stream owes nothing
+    let now = get_now_timestamp()?;
+    if vault.reward_streams[..vault.reward_stream_count as usize]
+        .iter()
+        .any(|stream| {
+            let never_funded = stream.reward_duration_deadline == 0 && stream.reward_liability == 0;
+            !never_funded
+                && (stream.reward_duration_deadline == 0 || stream.reward_duration_deadline >= now)
+        })
+    {
+        return Err(ErrorCode::RewardPeriodNotEnded.into());
+    }
+    // funders are refunded through their contributions, only what is left after them is refunded
+    if vault.reward_streams[..vault.reward_stream_count as usize]
+        .iter()
+        .any(|stream| stream.contribution_count > 0)
+    {
+        return Err(ErrorCode::ContributionsNotSettled.into());
+    }
+
     let vault_address = vault.key().clone();
     let (_vault_pda, vault_bump) = Pubkey::find_program_address(
         &[VAULT_REWARD_SEED.as_bytes(), vault_address.as_ref()],
@@ -69,46 +80,71 @@ pub fn close_vault(ctx: Context<CloseVault>) -> ProgramResult {
         vault_address.as_ref(),
         &[vault_bump],
     ];
-    if ctx.accounts.refund_account.owner == &System::id() {
-        let cpi_context = Create {
-            payer: ctx.accounts.authority.to_account_info(),
-            associated_token: ctx.accounts.refund_account.to_account_info(),
-            authority: ctx.accounts.refundee.to_account_info(),
-            mint: ctx.accounts.reward_mint.clone(),
-            rent: ctx.accounts.rent.to_account_info(),
-            token_program: ctx.accounts.token_program.to_account_info(),
-            system_program: ctx.accounts.system_program.to_account_info(),
-        };
-        let create_ctx = CpiContext::new(
-            ctx.accounts.associated_token_program.to_account_info(),
-            cpi_context,
+
+    let mut amounts = [0u64; MAX_REWARD_STREAMS];
+    let accounts = &mut ctx.remaining_accounts.iter();
+    for (index, stream) in vault.reward_streams[..vault.reward_stream_count as usize]
+        .iter()
+        .enumerate()
+    {
+        let reward_mint = accounts.next().ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+        let reward_mint_account_info =
+            accounts.next().ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+        let refund_account = accounts.next().ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+        if stream.reward_mint != *reward_mint.key
+            || stream.reward_mint_account != *reward_mint_account_info.key
+        {
+            return Err(ErrorCode::RewardStreamDoesNotExist.into());
+        }
+        let reward_mint_account = Account::<TokenAccount>::try_from(reward_mint_account_info)?;
+
+        if refund_account.owner == &System::id() {
+            let cpi_context = Create {
+                payer: ctx.accounts.authority.to_account_info(),
+                associated_token: refund_account.clone(),
+                authority: ctx.accounts.refundee.to_account_info(),
+                mint: reward_mint.clone(),
+                rent: ctx.accounts.rent.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            };
+            let create_ctx = CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                cpi_context,
+            );
+            create(create_ctx)?;
+        }
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info().clone(),
+            anchor_spl::token::Transfer {
+                from: reward_mint_account_info.clone(),
+                to: refund_account.clone(),
+                authority: ctx.accounts.reward.to_account_info().clone(),
+            },
         );
-        create(create_ctx)?;
-    }
 
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info().clone(),
-        anchor_spl::token::Transfer {
-            from: ctx.accounts.reward_mint_account.to_account_info().clone(),
-            to: ctx.accounts.refund_account.to_account_info().clone(),
-            authority: ctx.accounts.reward.to_account_info().clone(),
-        },
-    );
+        let refund_amount = reward_mint_account.amount;
+        anchor_spl::token::transfer(cpi_ctx.with_signer(&[&seeds[..]]), refund_amount)?;
+        amounts[index] = refund_amount;
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info().clone(),
+            anchor_spl::token::CloseAccount {
+                account: reward_mint_account_info.clone(),
+                destination: ctx.accounts.refundee.to_account_info().clone(),
+                authority: ctx.accounts.reward.to_account_info().clone(),
+            },
+        );
 
-    anchor_spl::token::transfer(
-        cpi_ctx.with_signer(&[&seeds[..]]),
-        ctx.accounts.reward_mint_account.amount,
-    )?;
-
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info().clone(),
-        anchor_spl::token::CloseAccount {
-            account: ctx.accounts.reward_mint_account.to_account_info().clone(),
-            destination: ctx.accounts.refundee.to_account_info().clone(),
-            authority: ctx.accounts.reward.to_account_info().clone(),
-        },
-    );
+        anchor_spl::token::close_account(cpi_ctx.with_signer(&[&seeds[..]]))?;
+    }
 
-    anchor_spl::token::close_account(cpi_ctx.with_signer(&[&seeds[..]]))?;
+    emit!(VaultClosed {
+        vault: vault_address,
+        refundee: ctx.accounts.refundee.key(),
+        amounts,
+        timestamp: now,
+    });
     Ok(())
 }
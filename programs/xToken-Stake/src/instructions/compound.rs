@@ -0,0 +1,132 @@
+use crate::constant::{CALC_PRECISION, VAULT_ESCROW_SEED, VAULT_REWARD_SEED};
+use crate::events::Compounded;
+use crate::state::{ErrorCode, StakeMode, User, Vault, VaultStatus};
+use crate::util::{get_now_timestamp, update_rewards};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+#[derive(Accounts)]
+#[instruction(escrow_bump: u8)]
+pub struct Compound<'info> {
+    // staker
+    staker: Signer<'info>,
+
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+
+    // reward pda account
+    #[account(
+    seeds = [VAULT_REWARD_SEED.as_bytes(), vault.key().as_ref()], bump = vault.reward_bump)]
+    reward: AccountInfo<'info>,
+
+    // reward account of the stream paying in the stake mint
+    #[account(mut,
+    constraint = reward_account.mint == vault.stake_mint)]
+    reward_account: Box<Account<'info, TokenAccount>>,
+
+    // escrow pda owning the deposited tokens
+    #[account(
+    seeds = [VAULT_ESCROW_SEED.as_bytes(), vault.key().as_ref()], bump = escrow_bump)]
+    escrow: SystemAccount<'info>,
+
+    // escrow token account, created by the first deposit
+    #[account(mut,
+    constraint = escrow_account.owner == escrow.key(),
+    constraint = escrow_account.mint == vault.stake_mint)]
+    escrow_account: Box<Account<'info, TokenAccount>>,
+
+    // user
+    #[account(mut,
+    constraint = user.vault == vault.key(),
+    constraint = user.key == *staker.key)]
+    user: Account<'info, User>,
+
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+}
+
+// restakes the pending rewards of the stream paying in the stake mint, they move from the reward account
+// into the escrow and count as deposited
+pub fn compound(ctx: Context<Compound>, _escrow_bump: u8) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Initialized {
+        return Err(ErrorCode::CanNotStake.into());
+    }
+
+    if vault.stake_mode != StakeMode::Fungible {
+        return Err(ErrorCode::InvalidStakeMode.into());
+    }
+
+    let index = vault
+        .reward_stream_index(&ctx.accounts.reward_account.key())
+        .ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+
+    // rewards must be updated before the staked weights change
+    let user = &mut ctx.accounts.user;
+    update_rewards(vault, user)?;
+
+    let reward = &mut user.rewards[index];
+    let amount = reward.reward_earned_pending;
+    if amount == 0 {
+        return Err(ErrorCode::ZeroAmount.into());
+    }
+
+    let vault_address = vault.key();
+    let seeds = &[
+        VAULT_REWARD_SEED.as_bytes(),
+        vault_address.as_ref(),
+        &[vault.reward_bump],
+    ];
+
+    let cpi_context = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        anchor_spl::token::Transfer {
+            from: ctx.accounts.reward_account.to_account_info(),
+            to: ctx.accounts.escrow_account.to_account_info(),
+            authority: ctx.accounts.reward.to_account_info(),
+        },
+    );
+
+    anchor_spl::token::transfer(cpi_context.with_signer(&[&seeds[..]]), amount)?;
+
+    // the restaked rewards are paid out of the stream
+    reward.reward_earned_pending = 0;
+    reward.reward_earned_claimed = reward
+        .reward_earned_claimed
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let stream = &mut vault.reward_streams[index];
+    stream.reward_liability = stream
+        .reward_liability
+        .checked_sub(
+            (amount as u128)
+                .checked_mul(CALC_PRECISION)
+                .ok_or(ErrorCode::MathOverflow)?,
+        )
+        .ok_or(ErrorCode::MathOverflow)?;
+    let reward_mint = stream.reward_mint;
+
+    user.staked_weight = user
+        .staked_weight
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    vault.staked_weight = vault
+        .staked_weight
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(Compounded {
+        vault: vault_address,
+        user: user.key(),
+        staker: ctx.accounts.staker.key(),
+        reward_mint,
+        amount,
+        user_staked_weight: user.staked_weight,
+        vault_staked_weight: vault.staked_weight,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::constant::VAULT_ROLE_SEED;
+use crate::events::{RoleGranted, RoleRevoked};
+use crate::state::{ErrorCode, Role, Vault, VaultRole, VAULT_ROLE_SIZE};
+use crate::util::get_now_timestamp;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+#[instruction(role_bump: u8, holder: Pubkey, role: Role, reward_mint: Pubkey)]
+pub struct GrantRole<'info> {
+    // vault authority
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(has_one = authority)]
+    vault: Account<'info, Vault>,
+
+    // role entry to be created, a funder role is scoped to one reward stream
+    #[account(init,
+    payer = authority,
+    seeds = [
+        VAULT_ROLE_SEED.as_bytes(), vault.key().as_ref(), holder.as_ref(), &[role as u8],
+        reward_mint.as_ref()
+    ],
+    bump = role_bump, space = VAULT_ROLE_SIZE)]
+    vault_role: Account<'info, VaultRole>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeRole<'info> {
+    // vault authority
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(has_one = authority)]
+    vault: Account<'info, Vault>,
+
+    // role entry to be closed
+    #[account(mut,
+    close = authority,
+    constraint = vault_role.vault == *vault.to_account_info().key)]
+    vault_role: Account<'info, VaultRole>,
+}
+
+pub fn grant_role(
+    ctx: Context<GrantRole>,
+    _role_bump: u8,
+    holder: Pubkey,
+    role: Role,
+    reward_mint: Pubkey,
+) -> ProgramResult {
+    let scoped = match role {
+        Role::Funder => ctx.accounts.vault.has_reward_mint(&reward_mint),
+        _ => reward_mint == Pubkey::default(),
+    };
+    if !scoped {
+        return Err(ErrorCode::InvalidRoleRewardMint.into());
+    }
+
+    let vault_role = &mut ctx.accounts.vault_role;
+    vault_role.vault = ctx.accounts.vault.key();
+    vault_role.holder = holder;
+    vault_role.role = role;
+    vault_role.reward_mint = reward_mint;
+
+    emit!(RoleGranted {
+        vault: vault_role.vault,
+        holder,
+        role,
+        reward_mint,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+pub fn revoke_role(ctx: Context<RevokeRole>) -> ProgramResult {
+    let vault_role = &ctx.accounts.vault_role;
+
+    emit!(RoleRevoked {
+        vault: vault_role.vault,
+        holder: vault_role.holder,
+        role: vault_role.role,
+        reward_mint: vault_role.reward_mint,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
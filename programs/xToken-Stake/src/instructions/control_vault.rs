@@ -0,0 +1,292 @@
+use crate::constant::{MAX_LOCK_TIERS, MIN_DURATION, MULTIPLIER_BASE, PENALTY_BASE};
+use crate::events::{
+    AuthorityProposed, AuthorityTransferred, CollectionSet, EmissionScheduleSet, LockTiersUpdated,
+    VaultConfigUpdated, VaultPaused, VaultResumed,
+};
+use crate::rewards::verify_emission_schedule;
+use crate::state::{EmissionSchedule, ErrorCode, LockTier, Role, Vault, VaultConfig, VaultStatus};
+use crate::util::{get_now_timestamp, update_reward_per_token, verify_role};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct ControlVault<'info> {
+    // vault authority or a holder of the role the instruction requires,
+    // whose role entry is the first remaining account
+    #[account(signer)]
+    authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(signer)]
+    pending_authority: AccountInfo<'info>,
+
+    #[account(mut, has_one = pending_authority)]
+    vault: Account<'info, Vault>,
+}
+
+pub fn pause_vault(ctx: Context<ControlVault>) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Pauser, Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Initialized {
+        return Err(ErrorCode::VaultNotReady.into());
+    }
+
+    // settle rewards up to now, nothing accrues until the vault is resumed
+    let now = get_now_timestamp()?;
+    update_reward_per_token(vault, now)?;
+
+    vault.status = VaultStatus::Paused;
+    vault.paused_at = now;
+
+    emit!(VaultPaused {
+        vault: vault.key(),
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+pub fn resume_vault(ctx: Context<ControlVault>) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Pauser, Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Paused {
+        return Err(ErrorCode::VaultNotPaused.into());
+    }
+
+    let now = get_now_timestamp()?;
+    let paused_duration = now
+        .checked_sub(vault.paused_at)
+        .ok_or(ErrorCode::InvalidTimestamp)?;
+
+    // extend running reward periods by the paused duration and skip the paused window,
+    // the emission curve resumes where it was paused
+    let paused_at = vault.paused_at;
+    let count = vault.reward_stream_count as usize;
+    for stream in vault.reward_streams[..count].iter_mut() {
+        if stream.reward_duration_deadline > paused_at {
+            stream.reward_duration_deadline = stream
+                .reward_duration_deadline
+                .checked_add(paused_duration)
+                .ok_or(ErrorCode::MathOverflow)?;
+            stream.period_start = stream
+                .period_start
+                .checked_add(paused_duration)
+                .ok_or(ErrorCode::MathOverflow)?;
+            stream.last_update_time = now;
+        }
+    }
+
+    vault.status = VaultStatus::Initialized;
+    vault.paused_at = 0;
+
+    emit!(VaultResumed {
+        vault: vault.key(),
+        paused_duration,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// tiers only apply to new stakes, existing stakes keep the terms stored on their receipts
+pub fn set_lock_tiers(
+    ctx: Context<ControlVault>,
+    lock_tiers: Vec<LockTier>,
+    early_unstake_penalty: u16,
+) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    if lock_tiers.len() > MAX_LOCK_TIERS {
+        return Err(ErrorCode::TooManyLockTiers.into());
+    }
+
+    if lock_tiers
+        .iter()
+        .any(|tier| tier.duration == 0 || tier.multiplier < MULTIPLIER_BASE)
+    {
+        return Err(ErrorCode::InvalidLockTier.into());
+    }
+
+    if early_unstake_penalty > PENALTY_BASE {
+        return Err(ErrorCode::InvalidPenalty.into());
+    }
+
+    let vault = &mut ctx.accounts.vault;
+    vault.lock_tiers = Default::default();
+    vault.lock_tiers[..lock_tiers.len()].copy_from_slice(&lock_tiers);
+    vault.lock_tier_count = lock_tiers.len() as u8;
+    vault.early_unstake_penalty = early_unstake_penalty;
+
+    emit!(LockTiersUpdated {
+        vault: vault.key(),
+        lock_tiers,
+        early_unstake_penalty,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+// only new stakes are checked, the default key accepts tokens of any collection
+pub fn set_collection(ctx: Context<ControlVault>, collection: Pubkey) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    vault.collection = collection;
+
+    emit!(CollectionSet {
+        vault: vault.key(),
+        collection,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+// a new reward duration applies from the next funding of the stream, the running period keeps its deadline
+pub fn update_vault_config(ctx: Context<ControlVault>, config: VaultConfig) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    let index = config.reward_index as usize;
+    if index >= vault.reward_stream_count as usize {
+        return Err(ErrorCode::RewardStreamDoesNotExist.into());
+    }
+
+    let old_reward_duration = vault.reward_streams[index].reward_duration;
+    let old_stake_token_count = vault.stake_token_count;
+    let old_max_stake_per_user = vault.max_stake_per_user;
+
+    if let Some(reward_duration) = config.reward_duration {
+        if reward_duration < MIN_DURATION {
+            return Err(ErrorCode::DurationTooShort.into());
+        }
+        vault.reward_streams[index].reward_duration = reward_duration;
+    }
+
+    if let Some(stake_token_count) = config.stake_token_count {
+        if stake_token_count < vault.staked_count {
+            return Err(ErrorCode::InvalidStakeTokenCount.into());
+        }
+        vault.stake_token_count = stake_token_count;
+    }
+
+    if let Some(max_stake_per_user) = config.max_stake_per_user {
+        vault.max_stake_per_user = max_stake_per_user;
+    }
+
+    emit!(VaultConfigUpdated {
+        vault: vault.key(),
+        reward_index: config.reward_index,
+        old_reward_duration,
+        new_reward_duration: vault.reward_streams[index].reward_duration,
+        old_stake_token_count,
+        new_stake_token_count: vault.stake_token_count,
+        old_max_stake_per_user,
+        new_max_stake_per_user: vault.max_stake_per_user,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+// the schedule shapes the next funding period of the stream, a running period keeps its curve
+pub fn set_emission_schedule(
+    ctx: Context<ControlVault>,
+    reward_index: u8,
+    schedule: EmissionSchedule,
+) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    verify_emission_schedule(&schedule)?;
+    let vault = &mut ctx.accounts.vault;
+    let index = reward_index as usize;
+    if index >= vault.reward_stream_count as usize {
+        return Err(ErrorCode::RewardStreamDoesNotExist.into());
+    }
+
+    let now = get_now_timestamp()?;
+    let stream = &mut vault.reward_streams[index];
+    if stream.reward_duration_deadline > now {
+        return Err(ErrorCode::RewardPeriodNotEnded.into());
+    }
+    stream.schedule = schedule;
+
+    emit!(EmissionScheduleSet {
+        vault: vault.key(),
+        reward_mint: vault.reward_streams[index].reward_mint,
+        schedule,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// the authority moves only once the proposed key accepts, proposing the default key cancels,
+// no role can propose
+pub fn propose_authority(ctx: Context<ControlVault>, pending_authority: Pubkey) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    vault.pending_authority = pending_authority;
+
+    emit!(AuthorityProposed {
+        vault: vault.key(),
+        authority: vault.authority,
+        pending_authority,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+pub fn accept_authority(ctx: Context<AcceptAuthority>) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    let old_authority = vault.authority;
+    let new_authority = vault.pending_authority;
+    vault.authority = new_authority;
+    vault.pending_authority = Pubkey::default();
+
+    emit!(AuthorityTransferred {
+        vault: vault.key(),
+        old_authority,
+        new_authority,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use crate::constant::VAULT_WEIGHT_SEED;
+use crate::events::{MintWeightRemoved, MintWeightSet};
+use crate::state::{ErrorCode, MintWeight, Role, Vault, MINT_WEIGHT_SIZE};
+use crate::util::{get_now_timestamp, verify_role};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+#[instruction(weight_bump: u8, mint: Pubkey)]
+pub struct SetMintWeight<'info> {
+    // vault authority or a holder of the required role, whose role entry is the first remaining account
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+
+    // weight entry, created on the first update
+    #[account(init_if_needed,
+    payer = authority,
+    seeds = [
+        VAULT_WEIGHT_SEED.as_bytes(), vault.key().as_ref(), mint.as_ref()
+    ],
+    bump = weight_bump, space = MINT_WEIGHT_SIZE)]
+    mint_weight: Account<'info, MintWeight>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveMintWeight<'info> {
+    // vault authority or a holder of the required role, whose role entry is the first remaining account
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+
+    // weight entry to be closed
+    #[account(mut,
+    close = authority,
+    constraint = mint_weight.vault == *vault.to_account_info().key)]
+    mint_weight: Account<'info, MintWeight>,
+}
+
+// weights only apply to new stakes, staked tokens keep the weight stored on their receipts
+pub fn set_mint_weight(
+    ctx: Context<SetMintWeight>,
+    _weight_bump: u8,
+    mint: Pubkey,
+    weight: u32,
+) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    if weight == 0 {
+        return Err(ErrorCode::InvalidWeight.into());
+    }
+
+    let vault = &mut ctx.accounts.vault;
+    let mint_weight = &mut ctx.accounts.mint_weight;
+    if mint_weight.vault == Pubkey::default() {
+        vault.weighted_mints = vault
+            .weighted_mints
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    mint_weight.vault = vault.key();
+    mint_weight.mint = mint;
+    mint_weight.weight = weight;
+
+    emit!(MintWeightSet {
+        vault: vault.key(),
+        mint,
+        weight,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+pub fn remove_mint_weight(ctx: Context<RemoveMintWeight>) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    vault.weighted_mints = vault
+        .weighted_mints
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(MintWeightRemoved {
+        vault: vault.key(),
+        mint: ctx.accounts.mint_weight.mint,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
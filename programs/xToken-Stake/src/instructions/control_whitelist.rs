@@ -0,0 +1,126 @@
+use crate::constant::VAULT_WHITELIST_SEED;
+use crate::events::{WhitelistAdded, WhitelistRemoved};
+use crate::state::{
+    ErrorCode, Role, Vault, VaultStatus, VaultWhitelist, WhitelistType, VAULT_WHITELIST_SIZE,
+};
+use crate::util::{get_now_timestamp, verify_role};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+#[instruction(whitelist_bump: u8, address: Pubkey)]
+pub struct AddWhitelist<'info> {
+    // vault authority or a holder of the required role, whose role entry is the first remaining account
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(mut,
+    constraint = vault.status == VaultStatus::Initialized)]
+    vault: Account<'info, Vault>,
+
+    // whitelist entry to be created
+    #[account(init,
+    payer = authority,
+    seeds = [
+        VAULT_WHITELIST_SEED.as_bytes(), vault.key().as_ref(), address.as_ref()
+    ],
+    bump = whitelist_bump, space = VAULT_WHITELIST_SIZE)]
+    whitelist: Account<'info, VaultWhitelist>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveWhitelist<'info> {
+    // vault authority or a holder of the required role, whose role entry is the first remaining account
+    #[account(mut)]
+    authority: Signer<'info>,
+
+    // vault
+    #[account(mut,
+    constraint = vault.status == VaultStatus::Initialized)]
+    vault: Account<'info, Vault>,
+
+    // whitelist entry to be closed
+    #[account(mut,
+    close = authority,
+    constraint = whitelist.vault == *vault.to_account_info().key)]
+    whitelist: Account<'info, VaultWhitelist>,
+}
+
+pub fn add_whitelist(
+    ctx: Context<AddWhitelist>,
+    _whitelist_bump: u8,
+    address: Pubkey,
+    whitelist_type: WhitelistType,
+) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::WhitelistManager, Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let whitelist = &mut ctx.accounts.whitelist;
+    whitelist.vault = ctx.accounts.vault.key();
+    whitelist.address = address;
+    whitelist.whitelist_type = whitelist_type;
+
+    let vault = &mut ctx.accounts.vault;
+    match whitelist_type {
+        WhitelistType::Mint => {
+            vault.whitelisted_mints = vault
+                .whitelisted_mints
+                .checked_add(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        WhitelistType::Creator => {
+            vault.whitelisted_creators = vault
+                .whitelisted_creators
+                .checked_add(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+    }
+
+    emit!(WhitelistAdded {
+        vault: vault.key(),
+        address,
+        whitelist_type,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
+
+pub fn remove_whitelist(ctx: Context<RemoveWhitelist>) -> ProgramResult {
+    verify_role(
+        &ctx.accounts.vault,
+        ctx.accounts.authority.key,
+        &[Role::WhitelistManager, Role::Admin],
+        ctx.remaining_accounts,
+    )?;
+    let vault = &mut ctx.accounts.vault;
+    let whitelist = &ctx.accounts.whitelist;
+    match whitelist.whitelist_type {
+        WhitelistType::Mint => {
+            vault.whitelisted_mints = vault
+                .whitelisted_mints
+                .checked_sub(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        WhitelistType::Creator => {
+            vault.whitelisted_creators = vault
+                .whitelisted_creators
+                .checked_sub(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+    }
+
+    emit!(WhitelistRemoved {
+        vault: vault.key(),
+        address: whitelist.address,
+        whitelist_type: whitelist.whitelist_type,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use crate::constant::VAULT_USER_SEED;
-use crate::state::{User, Vault, VaultStatus, USER_SIZE};
+use crate::events::UserCreated;
+use crate::state::{ErrorCode, User, Vault, VaultStatus, USER_SIZE};
 use crate::util::get_now_timestamp;
 
 use anchor_lang::prelude::*;
@@ -32,14 +33,22 @@ pub fn create_user(ctx: Context<CreateUser>, _user_bump: u8) -> ProgramResult {
     let user = &mut ctx.accounts.user;
     user.vault = *ctx.accounts.vault.to_account_info().key;
     user.key = *ctx.accounts.authority.key;
-    user.reward_earned_claimed = 0;
-    user.reward_earned_pending = 0;
+    user.rewards = Default::default();
     user.mint_staked_count = 0;
-    user.mint_accounts = vec![];
-    user.last_stake_time = get_now_timestamp();
+    user.last_stake_time = get_now_timestamp()?;
 
     let vault = &mut ctx.accounts.vault;
-    vault.user_count = vault.user_count.checked_add(1).unwrap();
+    vault.user_count = vault
+        .user_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(UserCreated {
+        vault: vault.key(),
+        user: user.key(),
+        authority: user.key,
+        timestamp: user.last_stake_time,
+    });
 
     Ok(())
 }
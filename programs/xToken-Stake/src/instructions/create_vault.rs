@@ -1,5 +1,9 @@
 use crate::constant::{MIN_DURATION, VAULT_REWARD_SEED};
-use crate::state::{ErrorCode, Vault, VaultStatus};
+use crate::events::VaultCreated;
+use crate::state::{
+    CustodyMode, ErrorCode, RewardStream, StakeMode, Vault, VaultStatus, VAULT_SIZE,
+};
+use crate::util::get_now_timestamp;
 
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::{create, AssociatedToken, Create};
@@ -13,7 +17,7 @@ pub struct CreateVault<'info> {
     authority: Signer<'info>,
 
     // vault account to be created
-    #[account(init, payer=authority)]
+    #[account(init, payer=authority, space = VAULT_SIZE)]
     vault: Account<'info, Vault>,
 
     // reward pda account
@@ -43,6 +47,9 @@ pub fn create_vault(
     reward_bump: u8,
     reward_duration: u64,
     stake_token_count: u32,
+    stake_mode: StakeMode,
+    stake_mint: Pubkey,
+    custody_mode: CustodyMode,
 ) -> ProgramResult {
     // check reward_duration
     if reward_duration < MIN_DURATION {
@@ -74,14 +81,38 @@ pub fn create_vault(
     }
 
     vault.authority = *ctx.accounts.authority.key;
-    vault.reward_mint = *ctx.accounts.reward_mint.to_account_info().key;
-    vault.reward_mint_account = ctx.accounts.reward_account.key();
+    // the first reward stream, partners can add their own with add_reward_stream
+    vault.reward_streams[0] = RewardStream {
+        reward_mint: *ctx.accounts.reward_mint.to_account_info().key,
+        reward_mint_account: ctx.accounts.reward_account.key(),
+        reward_duration,
+        ..RewardStream::default()
+    };
+    vault.reward_stream_count = 1;
     vault.stake_token_count = stake_token_count;
-    vault.reward_duration = reward_duration;
+    vault.stake_mode = stake_mode;
+    // only a fungible vault has a stake mint, its stakers deposit into a vault owned escrow
+    if stake_mode == StakeMode::Fungible {
+        vault.stake_mint = stake_mint;
+    }
+    vault.custody_mode = custody_mode;
     vault.reward_bump = reward_bump;
     vault.status = VaultStatus::Initialized;
     vault.staked_count = 0;
+    vault.staked_weight = 0;
     vault.user_count = 0;
 
+    emit!(VaultCreated {
+        vault: vault.key(),
+        authority: vault.authority,
+        reward_mint: vault.reward_streams[0].reward_mint,
+        reward_duration,
+        stake_token_count,
+        stake_mode,
+        stake_mint: vault.stake_mint,
+        custody_mode,
+        timestamp: get_now_timestamp()?,
+    });
+
     Ok(())
 }
@@ -0,0 +1,127 @@
+use crate::constant::VAULT_ESCROW_SEED;
+use crate::events::Deposited;
+use crate::state::{ErrorCode, StakeMode, User, Vault, VaultStatus};
+use crate::util::{get_now_timestamp, update_rewards};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{create, AssociatedToken, Create};
+use anchor_spl::token::{Token, TokenAccount};
+
+#[derive(Accounts)]
+#[instruction(escrow_bump: u8)]
+pub struct Deposit<'info> {
+    // authority
+    #[account(mut)]
+    staker: Signer<'info>,
+    // vault
+    #[account(mut,
+    has_one = stake_mint)]
+    vault: Account<'info, Vault>,
+    // escrow pda owning the deposited tokens
+    #[account(
+    seeds = [VAULT_ESCROW_SEED.as_bytes(), vault.key().as_ref()], bump = escrow_bump
+    )]
+    escrow: SystemAccount<'info>,
+    // stake token
+    stake_mint: AccountInfo<'info>,
+    // escrow token account, created on the first deposit
+    #[account(mut)]
+    escrow_account: UncheckedAccount<'info>,
+    // staker token account
+    #[account(mut,
+    constraint = staker_account.mint == vault.stake_mint,
+    constraint = staker_account.owner == *staker.key
+    )]
+    staker_account: Box<Account<'info, TokenAccount>>,
+    // user
+    #[account(mut,
+    constraint = user.vault == *vault.to_account_info().key,
+    constraint = user.key == *staker.key
+    )]
+    user: Account<'info, User>,
+    // rent
+    rent: Sysvar<'info, Rent>,
+    // associated token program
+    #[account(address = anchor_spl::associated_token::ID)]
+    associated_token_program: Program<'info, AssociatedToken>,
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+    // system program
+    system_program: Program<'info, System>,
+}
+
+pub fn deposit(ctx: Context<Deposit>, _escrow_bump: u8, amount: u64) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Initialized {
+        return Err(ErrorCode::CanNotStake.into());
+    }
+
+    if vault.stake_mode != StakeMode::Fungible {
+        return Err(ErrorCode::InvalidStakeMode.into());
+    }
+
+    if amount == 0 {
+        return Err(ErrorCode::ZeroAmount.into());
+    }
+
+    // create escrow token account
+    if ctx.accounts.escrow_account.owner == &System::id() {
+        let cpi_context = Create {
+            payer: ctx.accounts.staker.to_account_info(),
+            associated_token: ctx.accounts.escrow_account.to_account_info(),
+            authority: ctx.accounts.escrow.to_account_info(),
+            mint: ctx.accounts.stake_mint.clone(),
+            rent: ctx.accounts.rent.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        let create_ctx = CpiContext::new(
+            ctx.accounts.associated_token_program.to_account_info(),
+            cpi_context,
+        );
+        create(create_ctx)?;
+    }
+
+    let escrow_account = Account::<TokenAccount>::try_from(&ctx.accounts.escrow_account)?;
+    if escrow_account.owner != ctx.accounts.escrow.key() || escrow_account.mint != vault.stake_mint
+    {
+        return Err(ErrorCode::InvalidStakeAccount.into());
+    }
+
+    // rewards must be updated before the staked weights change
+    let user = &mut ctx.accounts.user;
+    update_rewards(vault, user)?;
+
+    user.staked_weight = user
+        .staked_weight
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    vault.staked_weight = vault
+        .staked_weight
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // transfer token
+    let cpi_context = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        anchor_spl::token::Transfer {
+            from: ctx.accounts.staker_account.to_account_info(),
+            to: ctx.accounts.escrow_account.to_account_info(),
+            authority: ctx.accounts.staker.to_account_info(),
+        },
+    );
+
+    anchor_spl::token::transfer(cpi_context, amount)?;
+
+    emit!(Deposited {
+        vault: vault.key(),
+        user: user.key(),
+        staker: ctx.accounts.staker.key(),
+        amount,
+        user_staked_weight: user.staked_weight,
+        vault_staked_weight: vault.staked_weight,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use crate::constant::{CALC_PRECISION, MAX_REWARD_STREAMS, VAULT_STAKE_SEED};
+use crate::custody::{release_custody, CustodyAccounts};
+use crate::events::EmergencyUnstaked;
+use crate::state::{ErrorCode, StakeReceipt, User, Vault};
+use crate::util::get_now_timestamp;
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use anchor_spl::token::TokenAccount;
+
+#[derive(Accounts)]
+#[instruction(vault_stake_bump: u8)]
+pub struct EmergencyUnstake<'info> {
+    // authority
+    #[account(mut)]
+    staker: Signer<'info>,
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+    // stake account
+    #[account(mut)]
+    unstake_account: Box<Account<'info, TokenAccount>>,
+    // stake receipt, closed back to the staker
+    #[account(mut,
+    close = staker,
+    has_one = vault,
+    constraint = receipt.owner == *staker.key,
+    constraint = receipt.stake_account == *unstake_account.to_account_info().key
+    )]
+    receipt: Account<'info, StakeReceipt>,
+    // vault pda
+    #[account(mut,
+    seeds = [VAULT_STAKE_SEED.as_bytes(), vault.key().as_ref(), staker.key().as_ref()], bump = vault_stake_bump
+    )]
+    vault_pda: AccountInfo<'info>,
+    // user
+    #[account(mut,
+    constraint = user.vault == *vault.to_account_info().key,
+    constraint = user.key == *staker.key
+    )]
+    user: Account<'info, User>,
+    // escrow token account of the vault pda, only used by escrow custody vaults
+    #[account(mut)]
+    escrow_account: AccountInfo<'info>,
+    // stake mint and vault freeze pda, only used by freeze custody vaults
+    stake_mint: AccountInfo<'info>,
+    freeze_authority: AccountInfo<'info>,
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+    // system program
+    system_program: Program<'info, System>,
+}
+
+// returns the staked token in any vault status without running reward math once its lock ended,
+// every reward of the user not claimed yet is forfeited
+pub fn emergency_unstake(ctx: Context<EmergencyUnstake>, vault_stake_bump: u8) -> ProgramResult {
+    let now = get_now_timestamp()?;
+    if now < ctx.accounts.receipt.lock_end_time {
+        return Err(ErrorCode::StakeLocked.into());
+    }
+
+    let vault = &mut ctx.accounts.vault;
+    let user = &mut ctx.accounts.user;
+    let unstake_account = &ctx.accounts.unstake_account;
+
+    // forfeited rewards are released from the stream liabilities, saturating so this never fails
+    let mut forfeited_rewards = [0u64; MAX_REWARD_STREAMS];
+    let count = vault.reward_stream_count as usize;
+    let balance_staked = user.staked_weight as u128;
+    for (index, stream) in vault.reward_streams[..count].iter_mut().enumerate() {
+        let reward = &mut user.rewards[index];
+        let forfeited_unsettled = stream
+            .reward_per_token_stored
+            .saturating_sub(reward.reward_per_token_paid)
+            .saturating_mul(balance_staked);
+        stream.reward_liability = stream
+            .reward_liability
+            .saturating_sub((reward.reward_earned_pending as u128).saturating_mul(CALC_PRECISION))
+            .saturating_sub(forfeited_unsettled);
+
+        forfeited_rewards[index] = reward.reward_earned_pending;
+        reward.reward_earned_pending = 0;
+        reward.reward_per_token_paid = stream.reward_per_token_stored;
+    }
+    user.mint_staked_count = user.mint_staked_count.saturating_sub(1);
+    if user.mint_staked_count == 0 {
+        user.lock_end_time = 0;
+    }
+    let weight = ctx.accounts.receipt.multiplier as u64;
+    user.staked_weight = user.staked_weight.saturating_sub(weight);
+    vault.staked_count = vault.staked_count.saturating_sub(1);
+    vault.staked_weight = vault.staked_weight.saturating_sub(weight);
+
+    // return the token
+    let vault_address = vault.key();
+    let staker_address = ctx.accounts.staker.key();
+
+    release_custody(
+        ctx.program_id,
+        vault,
+        &ctx.accounts.staker,
+        &ctx.accounts.vault_pda,
+        vault_stake_bump,
+        unstake_account,
+        ctx.accounts.receipt.amount,
+        &CustodyAccounts {
+            token_program: &ctx.accounts.token_program,
+            escrow_account: Some(&ctx.accounts.escrow_account),
+            stake_mint: Some(&ctx.accounts.stake_mint),
+            freeze_authority: Some(&ctx.accounts.freeze_authority),
+        },
+    )?;
+
+    emit!(EmergencyUnstaked {
+        vault: vault_address,
+        user: user.key(),
+        staker: staker_address,
+        mint: unstake_account.mint,
+        stake_account: unstake_account.key(),
+        forfeited_rewards,
+        timestamp: now,
+    });
+
+    Ok(())
+}
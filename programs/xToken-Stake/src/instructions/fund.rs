@@ -1,30 +1,27 @@
 use crate::{
-    constant::CALC_PRECISION,
-    state::{Vault, VaultStatus},
-    util::get_now_timestamp,
+    constant::VAULT_CONTRIBUTION_SEED,
+    events::Funded,
+    rewards::{fund_stream, last_time_reward_applicable},
+    state::{Contribution, ErrorCode, Vault, VaultStatus, CONTRIBUTION_SIZE},
+    util::{get_now_timestamp, update_reward_per_token, verify_funder},
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::TokenAccount;
 
 #[derive(Accounts)]
-#[instruction(amount: u64)]
+#[instruction(amount: u64, contribution_bump: u8)]
 pub struct Fund<'info> {
-    // funder
-    #[account(signer,
-    constraint = funder.key() == vault.authority || vault.funders.iter().any(|x| *x == funder.key())
-    )]
-    funder: AccountInfo<'info>,
-
-    // authority
-    authority: AccountInfo<'info>,
+    // funder, the vault authority, an admin or a funder of the funded stream,
+    // whose role entry is the first remaining account
+    #[account(mut)]
+    funder: Signer<'info>,
 
     // vault
-    #[account(mut, has_one = authority,
-    constraint = vault.status == VaultStatus::Initialized,
-    constraint = vault.reward_mint_account == * reward_account.to_account_info().key)]
+    #[account(mut,
+    constraint = vault.status == VaultStatus::Initialized)]
     vault: Account<'info, Vault>,
 
-    // reward account
+    // reward account of the funded stream
     #[account(mut)]
     reward_account: Box<Account<'info, TokenAccount>>,
 
@@ -33,51 +30,75 @@ pub struct Fund<'info> {
     constraint = funder_account.amount >= amount)]
     funder_account: Box<Account<'info, TokenAccount>>,
 
+    // contribution of the funder to the funded stream, created on the first funding and
+    // settled once the funded period ends
+    #[account(init_if_needed,
+    payer = funder,
+    seeds = [
+        VAULT_CONTRIBUTION_SEED.as_bytes(), vault.key().as_ref(),
+        reward_account.mint.as_ref(), funder.key().as_ref()
+    ],
+    bump = contribution_bump, space = CONTRIBUTION_SIZE)]
+    contribution: Account<'info, Contribution>,
+
     // token program
     #[account(address = spl_token::id())]
     token_program: AccountInfo<'info>,
+
+    // system program
+    system_program: Program<'info, System>,
 }
 
-pub fn fund(ctx: Context<Fund>, amount: u64) -> ProgramResult {
+pub fn fund(ctx: Context<Fund>, amount: u64, _contribution_bump: u8) -> ProgramResult {
     let vault = &mut ctx.accounts.vault;
-    let current_number = vault.stake_token_count;
-    let now = get_now_timestamp();
-
-    if now >= vault.reward_duration_deadline {
-        vault.reward_rate = (amount as u128)
-            .checked_mul(CALC_PRECISION)
-            .unwrap()
-            .checked_div(vault.reward_duration as u128)
-            .unwrap()
-            .checked_div(current_number as u128)
-            .unwrap();
-
-        vault.reward_duration_deadline = now.checked_add(vault.reward_duration).unwrap();
-
-        msg!("New reward deadline has been set");
-    } else {
-        let remaining = vault.reward_duration_deadline.checked_sub(now).unwrap();
-        let current_value = (vault.reward_rate as u128)
-            .checked_mul(remaining as u128)
-            .unwrap()
-            .checked_mul(current_number as u128)
-            .unwrap();
-
-        msg!("Current reward overall amount is {}", current_value);
-
-        vault.reward_rate = (amount as u128)
-            .checked_mul(CALC_PRECISION)
-            .unwrap()
-            .checked_add(current_value)
-            .unwrap()
-            .checked_div(vault.reward_duration as u128)
-            .unwrap()
-            .checked_div(current_number as u128)
-            .unwrap();
-
-        msg!("New reward rate is {}", vault.reward_rate);
+    let now = get_now_timestamp()?;
+
+    let index = vault
+        .reward_stream_index(&ctx.accounts.reward_account.key())
+        .ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+    verify_funder(
+        vault,
+        ctx.accounts.funder.key,
+        &ctx.accounts.reward_account.mint,
+        ctx.remaining_accounts,
+    )?;
+    if vault.reward_streams[index].refunding {
+        return Err(ErrorCode::RewardStreamRefunding.into());
     }
 
+    // settle rewards accrued under the current rate before changing it
+    update_reward_per_token(vault, now)?;
+
+    let last_time_reward_applicable =
+        last_time_reward_applicable(vault, &vault.reward_streams[index], now);
+    let stream = &mut vault.reward_streams[index];
+    fund_stream(
+        stream,
+        amount,
+        ctx.accounts.reward_account.amount,
+        last_time_reward_applicable,
+        now,
+    )?;
+    if ctx.accounts.contribution.funder == Pubkey::default() {
+        stream.contribution_count = stream
+            .contribution_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    let reward_mint = stream.reward_mint;
+    let reward_rate = stream.reward_rate;
+    let reward_duration_deadline = stream.reward_duration_deadline;
+
+    let contribution = &mut ctx.accounts.contribution;
+    contribution.vault = vault.key();
+    contribution.reward_mint = reward_mint;
+    contribution.funder = ctx.accounts.funder.key();
+    contribution.amount = contribution
+        .amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
     // transfer token
     let cpi_context = CpiContext::new(
         ctx.accounts.token_program.to_account_info(),
@@ -90,5 +111,16 @@ pub fn fund(ctx: Context<Fund>, amount: u64) -> ProgramResult {
 
     anchor_spl::token::transfer(cpi_context, amount)?;
 
+    emit!(Funded {
+        vault: vault.key(),
+        reward_mint,
+        funder: ctx.accounts.funder.key(),
+        amount,
+        contribution: contribution.amount,
+        reward_rate,
+        reward_duration_deadline,
+        timestamp: now,
+    });
+
     Ok(())
 }
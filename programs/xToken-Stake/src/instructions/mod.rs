@@ -1,19 +1,37 @@
+pub mod add_reward_stream;
 pub mod claim;
 pub mod close_user;
 pub mod close_vault;
-pub mod control_funder;
+pub mod compound;
+pub mod control_role;
+pub mod control_vault;
+pub mod control_weight;
+pub mod control_whitelist;
 pub mod create_user;
 pub mod create_vault;
+pub mod deposit;
+pub mod emergency_unstake;
 pub mod fund;
 pub mod stake;
 pub mod unstake;
+pub mod withdraw;
+pub mod withdraw_unallocated;
 
+pub use add_reward_stream::*;
 pub use claim::*;
 pub use close_user::*;
 pub use close_vault::*;
-pub use control_funder::*;
+pub use compound::*;
+pub use control_role::*;
+pub use control_vault::*;
+pub use control_weight::*;
+pub use control_whitelist::*;
 pub use create_user::*;
 pub use create_vault::*;
+pub use deposit::*;
+pub use emergency_unstake::*;
 pub use fund::*;
 pub use stake::*;
 pub use unstake::*;
+pub use withdraw::*;
+pub use withdraw_unallocated::*;
@@ -1,13 +1,19 @@
-use crate::constant::{MAX_MINT_LIMIT, VAULT_STAKE_SEED};
-use crate::state::{ErrorCode, VaultStatus};
+use crate::constant::{MULTIPLIER_BASE, VAULT_RECEIPT_SEED};
+use crate::custody::{take_custody, CustodyAccounts};
+use crate::events::Staked;
+use crate::state::{ErrorCode, LockTier, StakeMode, StakeReceipt, VaultStatus, STAKE_RECEIPT_SIZE};
 use crate::state::{User, Vault};
-use crate::util::update_rewards;
+use crate::util::{
+    create_pda_account, get_now_timestamp, mint_weight, update_rewards, verify_collection,
+    verify_whitelist,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::token::Token;
 use anchor_spl::token::TokenAccount;
-use spl_token::instruction::AuthorityType::AccountOwner;
+use std::convert::TryInto;
 
 #[derive(Accounts)]
+#[instruction(receipt_bump: u8)]
 pub struct Stake<'info> {
     // authority
     #[account(mut)]
@@ -17,16 +23,31 @@ pub struct Stake<'info> {
     vault: Account<'info, Vault>,
     // stake account
     #[account(mut,
-    constraint = !user.mint_accounts.iter().any(|x| x == stake_account.to_account_info().key),
     constraint = stake_account.amount > 0
     )]
     stake_account: Box<Account<'info, TokenAccount>>,
+    // stake receipt to be created, can not exist twice for the same stake account
+    #[account(init,
+    payer = staker,
+    seeds = [
+        VAULT_RECEIPT_SEED.as_bytes(), vault.key().as_ref(), stake_account.key().as_ref()
+    ],
+    bump = receipt_bump, space = STAKE_RECEIPT_SIZE)]
+    receipt: Account<'info, StakeReceipt>,
     // user
     #[account(mut,
     constraint = user.vault == *vault.to_account_info().key,
     constraint = user.key == *staker.key
     )]
     user: Account<'info, User>,
+    // vault pda of the staker, checked when taking custody
+    vault_pda: AccountInfo<'info>,
+    // escrow token account of the vault pda, only used by escrow custody vaults
+    #[account(mut)]
+    escrow_account: AccountInfo<'info>,
+    // stake mint and vault freeze pda, only used by freeze custody vaults
+    stake_mint: AccountInfo<'info>,
+    freeze_authority: AccountInfo<'info>,
     // token program
     #[account(address = spl_token::id())]
     token_program: Program<'info, Token>,
@@ -34,52 +55,243 @@ pub struct Stake<'info> {
     system_program: Program<'info, System>,
 }
 
-pub fn stake(ctx: Context<Stake>) -> ProgramResult {
+#[derive(Accounts)]
+pub struct StakeMany<'info> {
+    // authority
+    #[account(mut)]
+    staker: Signer<'info>,
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+    // user
+    #[account(mut,
+    constraint = user.vault == *vault.to_account_info().key,
+    constraint = user.key == *staker.key
+    )]
+    user: Account<'info, User>,
+    // vault pda of the staker, checked when taking custody
+    vault_pda: AccountInfo<'info>,
+    // vault freeze pda, only used by freeze custody vaults
+    freeze_authority: AccountInfo<'info>,
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+    // system program
+    system_program: Program<'info, System>,
+}
+
+pub fn stake(ctx: Context<Stake>, _receipt_bump: u8, lock_tier: Option<u8>) -> ProgramResult {
     let vault = &mut ctx.accounts.vault;
     if vault.status != VaultStatus::Initialized {
         return Err(ErrorCode::CanNotStake.into());
     }
 
+    if vault.stake_mode != StakeMode::Nft {
+        return Err(ErrorCode::InvalidStakeMode.into());
+    }
+
+    let tier = vault.lock_tier(lock_tier)?;
     let user = &mut ctx.accounts.user;
-    if user.mint_accounts.len() >= MAX_MINT_LIMIT {
-        return Err(ErrorCode::MaxStakeCountReached.into());
+    let stake_account = &ctx.accounts.stake_account;
+
+    // whitelist proof and metadata, the mint weight, then the collection mint and metadata
+    // are passed through remaining accounts
+    let proofs = &mut ctx.remaining_accounts.iter();
+    verify_whitelist(ctx.program_id, vault, &stake_account.mint, proofs)?;
+    let weight = mint_weight(ctx.program_id, vault, &stake_account.mint, proofs)?;
+    verify_collection(vault, &stake_account.mint, proofs)?;
+
+    update_rewards(vault, user)?;
+    set_stake_terms(&mut ctx.accounts.receipt, &tier, weight)?;
+    lock_stake_account(
+        vault,
+        user,
+        &ctx.accounts.staker,
+        stake_account,
+        &mut ctx.accounts.receipt,
+    )?;
+    take_custody(
+        ctx.program_id,
+        vault,
+        &ctx.accounts.staker,
+        &ctx.accounts.vault_pda,
+        stake_account,
+        &CustodyAccounts {
+            token_program: &ctx.accounts.token_program,
+            escrow_account: Some(&ctx.accounts.escrow_account),
+            stake_mint: Some(&ctx.accounts.stake_mint),
+            freeze_authority: Some(&ctx.accounts.freeze_authority),
+        },
+    )
+}
+
+// remaining accounts hold the token accounts to stake, each followed by its stake receipt,
+// by its escrow token account or its stake mint when the vault has escrow or freeze custody,
+// by its whitelist proof and metadata when the vault has a whitelist
+// by its mint weight when the vault has weighted mints
+// and by its mint and metadata when the vault has a collection
+pub fn stake_many<'info>(
+    ctx: Context<'_, '_, '_, 'info, StakeMany<'info>>,
+    lock_tier: Option<u8>,
+) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Initialized {
+        return Err(ErrorCode::CanNotStake.into());
     }
 
-    // Todo: Check stake account is one of the vault's staked accounts
-    // Vault staked accounts should be set ahead and user can stake only one of those a ccounts
+    if vault.stake_mode != StakeMode::Nft {
+        return Err(ErrorCode::InvalidStakeMode.into());
+    }
 
-    // update
-    let stake_account = &mut ctx.accounts.stake_account;
+    if ctx.remaining_accounts.is_empty() {
+        return Err(ErrorCode::NoStakeAccounts.into());
+    }
 
-    if user.mint_accounts.iter().any(|x| *x == stake_account.key()) {
-        return Err(ErrorCode::AlreadyStakedAccount.into());
-    } else {
-        update_rewards(vault, user).unwrap();
-        user.mint_staked_count = user.mint_staked_count.checked_add(1).unwrap();
-        vault.staked_count = vault.staked_count.checked_add(1).unwrap();
+    let tier = vault.lock_tier(lock_tier)?;
+    let user = &mut ctx.accounts.user;
+    update_rewards(vault, user)?;
 
-        user.mint_accounts.push(stake_account.key());
+    let accounts = &mut ctx.remaining_accounts.iter();
+    while let Some(stake_account_info) = accounts.next() {
+        let stake_account = Account::<TokenAccount>::try_from(stake_account_info)?;
+        let receipt_info = accounts.next().ok_or(ErrorCode::NoStakeAccounts)?;
+        let custody = CustodyAccounts::next(
+            vault,
+            &ctx.accounts.token_program,
+            &ctx.accounts.freeze_authority,
+            accounts,
+        )?;
+        verify_whitelist(ctx.program_id, vault, &stake_account.mint, accounts)?;
+        let weight = mint_weight(ctx.program_id, vault, &stake_account.mint, accounts)?;
+        verify_collection(vault, &stake_account.mint, accounts)?;
 
-        // transfer token authority
-        let (vault_pda, _vault_bump) = Pubkey::find_program_address(
+        // create stake receipt, fails when the stake account is staked already
+        let vault_address = vault.key();
+        let stake_account_address = stake_account.key();
+        let (receipt_address, receipt_bump) = Pubkey::find_program_address(
             &[
-                VAULT_STAKE_SEED.as_bytes(),
-                vault.key().as_ref(),
-                ctx.accounts.staker.key().as_ref(),
+                VAULT_RECEIPT_SEED.as_bytes(),
+                vault_address.as_ref(),
+                stake_account_address.as_ref(),
             ],
             ctx.program_id,
         );
+        if receipt_address != *receipt_info.key {
+            return Err(ErrorCode::InvalidStakeAccount.into());
+        }
+        if receipt_info.owner == ctx.program_id {
+            return Err(ErrorCode::AlreadyStakedAccount.into());
+        }
+        create_pda_account(
+            &ctx.accounts.staker.to_account_info(),
+            receipt_info,
+            &ctx.accounts.system_program.to_account_info(),
+            STAKE_RECEIPT_SIZE,
+            ctx.program_id,
+            &[
+                VAULT_RECEIPT_SEED.as_bytes(),
+                vault_address.as_ref(),
+                stake_account_address.as_ref(),
+                &[receipt_bump],
+            ],
+        )?;
+        let mut receipt = Account::<StakeReceipt>::try_from_unchecked(receipt_info)?;
+        set_stake_terms(&mut receipt, &tier, weight)?;
 
-        let cpi_context = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            anchor_spl::token::SetAuthority {
-                current_authority: ctx.accounts.staker.to_account_info().clone(),
-                account_or_mint: stake_account.to_account_info().clone(),
-            },
-        );
+        lock_stake_account(
+            vault,
+            user,
+            &ctx.accounts.staker,
+            &stake_account,
+            &mut receipt,
+        )?;
+        take_custody(
+            ctx.program_id,
+            vault,
+            &ctx.accounts.staker,
+            &ctx.accounts.vault_pda,
+            &stake_account,
+            &custody,
+        )?;
+        receipt.exit(ctx.program_id)?;
+    }
 
-        anchor_spl::token::set_authority(cpi_context, AccountOwner, Some(vault_pda))?;
+    Ok(())
+}
+
+// the staked weight is the mint weight scaled by the lock tier multiplier,
+// a locked stake can not be unstaked without penalty before its lock ends
+fn set_stake_terms(receipt: &mut StakeReceipt, tier: &LockTier, weight: u32) -> ProgramResult {
+    receipt.multiplier = (tier.multiplier as u64)
+        .checked_mul(weight as u64)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(MULTIPLIER_BASE as u64)
+        .ok_or(ErrorCode::MathOverflow)?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?;
+    receipt.lock_end_time = get_now_timestamp()?
+        .checked_add(tier.duration)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+// rewards must be updated before the staked counts change, the staked weight is the receipt multiplier,
+// the token is taken into custody afterwards
+fn lock_stake_account<'info>(
+    vault: &mut Account<'info, Vault>,
+    user: &mut Account<'info, User>,
+    staker: &Signer<'info>,
+    stake_account: &Account<'info, TokenAccount>,
+    receipt: &mut Account<'info, StakeReceipt>,
+) -> ProgramResult {
+    if vault.staked_count >= vault.stake_token_count {
+        return Err(ErrorCode::MaxStakeCountReached.into());
     }
 
+    if vault.max_stake_per_user > 0 && user.mint_staked_count >= vault.max_stake_per_user {
+        return Err(ErrorCode::MaxUserStakeCountReached.into());
+    }
+
+    if stake_account.amount == 0 || stake_account.owner != staker.key() {
+        return Err(ErrorCode::InvalidStakeAccount.into());
+    }
+
+    user.mint_staked_count = user
+        .mint_staked_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user.staked_weight = user
+        .staked_weight
+        .checked_add(receipt.multiplier as u64)
+        .ok_or(ErrorCode::MathOverflow)?;
+    vault.staked_count = vault
+        .staked_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    vault.staked_weight = vault
+        .staked_weight
+        .checked_add(receipt.multiplier as u64)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user.lock_end_time = std::cmp::max(user.lock_end_time, receipt.lock_end_time);
+
+    let now = get_now_timestamp()?;
+    receipt.vault = vault.key();
+    receipt.owner = staker.key();
+    receipt.mint = stake_account.mint;
+    receipt.stake_account = stake_account.key();
+    receipt.stake_time = now;
+    receipt.amount = stake_account.amount;
+
+    emit!(Staked {
+        vault: vault.key(),
+        user: user.key(),
+        staker: staker.key(),
+        mint: stake_account.mint,
+        stake_account: stake_account.key(),
+        user_staked_count: user.mint_staked_count,
+        vault_staked_count: vault.staked_count,
+        timestamp: now,
+    });
+
     Ok(())
 }
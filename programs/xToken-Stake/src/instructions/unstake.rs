@@ -1,11 +1,13 @@
 use crate::constant::VAULT_STAKE_SEED;
-use crate::state::{ErrorCode, VaultStatus};
+use crate::custody::{release_custody, CustodyAccounts};
+use crate::events::{EarlyUnstakePenalized, Unstaked};
+use crate::state::{ErrorCode, StakeReceipt, VaultStatus};
 use crate::state::{User, Vault};
-use crate::util::update_rewards;
+use crate::util::{apply_early_unstake_penalty, get_now_timestamp, update_rewards};
 use anchor_lang::prelude::*;
+use anchor_lang::AccountsClose;
 use anchor_spl::token::Token;
 use anchor_spl::token::TokenAccount;
-use spl_token::instruction::AuthorityType::AccountOwner;
 
 #[derive(Accounts)]
 #[instruction(vault_stake_bump: u8)]
@@ -16,12 +18,50 @@ pub struct Unstake<'info> {
     // vault
     #[account(mut)]
     vault: Account<'info, Vault>,
-    // stake account
+    // stake account, empty while its token is held in escrow
+    #[account(mut)]
+    unstake_account: Box<Account<'info, TokenAccount>>,
+    // stake receipt, closed back to the staker
     #[account(mut,
-    constraint = user.mint_accounts.iter().any(|x| x == unstake_account.to_account_info().key),
-    constraint = unstake_account.amount > 0
+    close = staker,
+    has_one = vault,
+    constraint = receipt.owner == *staker.key,
+    constraint = receipt.stake_account == *unstake_account.to_account_info().key
     )]
-    unstake_account: Box<Account<'info, TokenAccount>>,
+    receipt: Account<'info, StakeReceipt>,
+    // vault pda
+    #[account(mut,
+    seeds = [VAULT_STAKE_SEED.as_bytes(), vault.key().as_ref(), staker.key().as_ref()], bump = vault_stake_bump
+    )]
+    vault_pda: AccountInfo<'info>,
+    // user
+    #[account(mut,
+    constraint = user.vault == *vault.to_account_info().key,
+    constraint = user.key == *staker.key
+    )]
+    user: Account<'info, User>,
+    // escrow token account of the vault pda, only used by escrow custody vaults
+    #[account(mut)]
+    escrow_account: AccountInfo<'info>,
+    // stake mint and vault freeze pda, only used by freeze custody vaults
+    stake_mint: AccountInfo<'info>,
+    freeze_authority: AccountInfo<'info>,
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+    // system program
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_stake_bump: u8)]
+pub struct UnstakeMany<'info> {
+    // authority
+    #[account(mut)]
+    staker: Signer<'info>,
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
     // vault pda
     #[account(mut,
     seeds = [VAULT_STAKE_SEED.as_bytes(), vault.key().as_ref(), staker.key().as_ref()], bump = vault_stake_bump
@@ -33,6 +73,8 @@ pub struct Unstake<'info> {
     constraint = user.key == *staker.key
     )]
     user: Account<'info, User>,
+    // vault freeze pda, only used by freeze custody vaults
+    freeze_authority: AccountInfo<'info>,
     // token program
     #[account(address = spl_token::id())]
     token_program: Program<'info, Token>,
@@ -40,69 +82,159 @@ pub struct Unstake<'info> {
     system_program: Program<'info, System>,
 }
 
-pub fn unstake(ctx: Context<Unstake>, _vault_stake_bump: u8) -> ProgramResult {
+pub fn unstake(ctx: Context<Unstake>, vault_stake_bump: u8) -> ProgramResult {
     let vault = &mut ctx.accounts.vault;
-    if vault.status != VaultStatus::Initialized {
+    // unstake keeps working while paused so users are never locked in
+    if vault.status != VaultStatus::Initialized && vault.status != VaultStatus::Paused {
         return Err(ErrorCode::CanNotStake.into());
     }
 
     // update
     let user = &mut ctx.accounts.user;
-    let unstake_account = &mut ctx.accounts.unstake_account;
-
-    if !user
-        .mint_accounts
-        .iter()
-        .any(|x| *x == unstake_account.key())
-    {
-        return Err(ErrorCode::StakedAccountDoesNotExist.into());
-    } else {
-        update_rewards(vault, user).unwrap();
-        user.mint_staked_count = user.mint_staked_count.checked_sub(1).unwrap();
-        vault.staked_count = vault.staked_count.checked_sub(1).unwrap();
-
-        let index = user
-            .mint_accounts
-            .iter()
-            .position(|x| *x == unstake_account.key());
-        if let Some(existed) = index {
-            user.mint_accounts.remove(existed);
-        }
+    update_rewards(vault, user)?;
+    release_weight(vault, user, &ctx.accounts.receipt)?;
+    release_stake_account(
+        vault,
+        user,
+        &ctx.accounts.staker,
+        &ctx.accounts.unstake_account,
+    )?;
+    release_custody(
+        ctx.program_id,
+        vault,
+        &ctx.accounts.staker,
+        &ctx.accounts.vault_pda,
+        vault_stake_bump,
+        &ctx.accounts.unstake_account,
+        ctx.accounts.receipt.amount,
+        &CustodyAccounts {
+            token_program: &ctx.accounts.token_program,
+            escrow_account: Some(&ctx.accounts.escrow_account),
+            stake_mint: Some(&ctx.accounts.stake_mint),
+            freeze_authority: Some(&ctx.accounts.freeze_authority),
+        },
+    )
+}
+
+// remaining accounts hold the staked token accounts to return, each followed by its stake receipt
+// and by its escrow token account or its stake mint when the vault has escrow or freeze custody
+pub fn unstake_many<'info>(
+    ctx: Context<'_, '_, '_, 'info, UnstakeMany<'info>>,
+    vault_stake_bump: u8,
+) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    if vault.status != VaultStatus::Initialized && vault.status != VaultStatus::Paused {
+        return Err(ErrorCode::CanNotStake.into());
+    }
+
+    if ctx.remaining_accounts.is_empty() {
+        return Err(ErrorCode::NoStakeAccounts.into());
+    }
 
-        // transfer token authority
-        let vault_address = vault.key().clone();
-        let staker_address = ctx.accounts.staker.key().clone();
+    let user = &mut ctx.accounts.user;
+    update_rewards(vault, user)?;
+
+    let accounts = &mut ctx.remaining_accounts.iter();
+    while let Some(unstake_account_info) = accounts.next() {
+        let unstake_account = Account::<TokenAccount>::try_from(unstake_account_info)?;
+        let receipt_info = accounts.next().ok_or(ErrorCode::NoStakeAccounts)?;
+        let receipt = Account::<StakeReceipt>::try_from(receipt_info)?;
+        if receipt.vault != vault.key()
+            || receipt.owner != ctx.accounts.staker.key()
+            || receipt.stake_account != unstake_account.key()
+        {
+            return Err(ErrorCode::StakedAccountDoesNotExist.into());
+        }
+        let custody = CustodyAccounts::next(
+            vault,
+            &ctx.accounts.token_program,
+            &ctx.accounts.freeze_authority,
+            accounts,
+        )?;
 
-        let (_vault_pda, vault_bump) = Pubkey::find_program_address(
-            &[
-                VAULT_STAKE_SEED.as_bytes(),
-                vault_address.as_ref(),
-                staker_address.as_ref(),
-            ],
+        release_weight(vault, user, &receipt)?;
+        release_stake_account(vault, user, &ctx.accounts.staker, &unstake_account)?;
+        release_custody(
             ctx.program_id,
-        );
-
-        let seeds = &[
-            VAULT_STAKE_SEED.as_bytes(),
-            vault_address.as_ref(),
-            staker_address.as_ref(),
-            &[vault_bump],
-        ]; // need this to sign the pda, match the authority
-
-        let cpi_context = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            anchor_spl::token::SetAuthority {
-                current_authority: ctx.accounts.vault_pda.to_account_info().clone(),
-                account_or_mint: unstake_account.to_account_info().clone(),
-            },
-        );
-
-        anchor_spl::token::set_authority(
-            cpi_context.with_signer(&[&seeds[..]]),
-            AccountOwner,
-            Some(ctx.accounts.staker.key()),
+            vault,
+            &ctx.accounts.staker,
+            &ctx.accounts.vault_pda,
+            vault_stake_bump,
+            &unstake_account,
+            receipt.amount,
+            &custody,
         )?;
+        receipt.close(ctx.accounts.staker.to_account_info())?;
     }
 
     Ok(())
 }
+
+// rejects or penalizes an unstake before the lock ends, then releases the staked weight of the receipt,
+// rewards must be updated first
+fn release_weight(
+    vault: &mut Account<Vault>,
+    user: &mut Account<User>,
+    receipt: &StakeReceipt,
+) -> ProgramResult {
+    let now = get_now_timestamp()?;
+    let weight = receipt.multiplier as u64;
+    if now < receipt.lock_end_time {
+        if vault.early_unstake_penalty == 0 {
+            return Err(ErrorCode::StakeLocked.into());
+        }
+
+        let penalties = apply_early_unstake_penalty(vault, user, weight, now)?;
+        emit!(EarlyUnstakePenalized {
+            vault: vault.key(),
+            user: user.key(),
+            stake_account: receipt.stake_account,
+            penalties,
+            timestamp: now,
+        });
+    }
+
+    user.staked_weight = user
+        .staked_weight
+        .checked_sub(weight)
+        .ok_or(ErrorCode::MathOverflow)?;
+    vault.staked_weight = vault
+        .staked_weight
+        .checked_sub(weight)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+// rewards must be updated before the staked counts change, the token is released from custody afterwards
+fn release_stake_account<'info>(
+    vault: &mut Account<'info, Vault>,
+    user: &mut Account<'info, User>,
+    staker: &Signer<'info>,
+    unstake_account: &Account<'info, TokenAccount>,
+) -> ProgramResult {
+    user.mint_staked_count = user
+        .mint_staked_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    if user.mint_staked_count == 0 {
+        user.lock_end_time = 0;
+    }
+    vault.staked_count = vault
+        .staked_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(Unstaked {
+        vault: vault.key(),
+        user: user.key(),
+        staker: staker.key(),
+        mint: unstake_account.mint,
+        stake_account: unstake_account.key(),
+        user_staked_count: user.mint_staked_count,
+        vault_staked_count: vault.staked_count,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
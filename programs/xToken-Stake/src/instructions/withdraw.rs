@@ -0,0 +1,107 @@
+use crate::constant::VAULT_ESCROW_SEED;
+use crate::events::Withdrawn;
+use crate::state::{ErrorCode, StakeMode, User, Vault, VaultStatus};
+use crate::util::{get_now_timestamp, update_rewards};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+#[derive(Accounts)]
+#[instruction(escrow_bump: u8)]
+pub struct Withdraw<'info> {
+    // authority
+    #[account(mut)]
+    staker: Signer<'info>,
+    // vault
+    #[account(mut)]
+    vault: Account<'info, Vault>,
+    // escrow pda owning the deposited tokens
+    #[account(
+    seeds = [VAULT_ESCROW_SEED.as_bytes(), vault.key().as_ref()], bump = escrow_bump
+    )]
+    escrow: AccountInfo<'info>,
+    // escrow token account
+    #[account(mut,
+    constraint = escrow_account.mint == vault.stake_mint,
+    constraint = escrow_account.owner == *escrow.key
+    )]
+    escrow_account: Box<Account<'info, TokenAccount>>,
+    // staker token account
+    #[account(mut,
+    constraint = staker_account.mint == vault.stake_mint
+    )]
+    staker_account: Box<Account<'info, TokenAccount>>,
+    // user
+    #[account(mut,
+    constraint = user.vault == *vault.to_account_info().key,
+    constraint = user.key == *staker.key
+    )]
+    user: Account<'info, User>,
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+}
+
+// partial withdrawals are allowed, the remaining deposit keeps earning
+pub fn withdraw(ctx: Context<Withdraw>, escrow_bump: u8, amount: u64) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    // withdraw keeps working while paused so users are never locked in
+    if vault.status != VaultStatus::Initialized && vault.status != VaultStatus::Paused {
+        return Err(ErrorCode::CanNotStake.into());
+    }
+
+    if vault.stake_mode != StakeMode::Fungible {
+        return Err(ErrorCode::InvalidStakeMode.into());
+    }
+
+    if amount == 0 {
+        return Err(ErrorCode::ZeroAmount.into());
+    }
+
+    let user = &mut ctx.accounts.user;
+    if amount > user.staked_weight {
+        return Err(ErrorCode::InsufficientStake.into());
+    }
+
+    // rewards must be updated before the staked weights change
+    update_rewards(vault, user)?;
+
+    user.staked_weight = user
+        .staked_weight
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    vault.staked_weight = vault
+        .staked_weight
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // transfer token back to the staker
+    let vault_address = vault.key();
+    let seeds = &[
+        VAULT_ESCROW_SEED.as_bytes(),
+        vault_address.as_ref(),
+        &[escrow_bump],
+    ]; // need this to sign the pda, match the authority
+
+    let cpi_context = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        anchor_spl::token::Transfer {
+            from: ctx.accounts.escrow_account.to_account_info(),
+            to: ctx.accounts.staker_account.to_account_info(),
+            authority: ctx.accounts.escrow.clone(),
+        },
+    );
+
+    anchor_spl::token::transfer(cpi_context.with_signer(&[&seeds[..]]), amount)?;
+
+    emit!(Withdrawn {
+        vault: vault_address,
+        user: user.key(),
+        staker: ctx.accounts.staker.key(),
+        amount,
+        user_staked_weight: user.staked_weight,
+        vault_staked_weight: vault.staked_weight,
+        timestamp: get_now_timestamp()?,
+    });
+
+    Ok(())
+}
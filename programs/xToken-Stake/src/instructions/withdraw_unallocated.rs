@@ -0,0 +1,102 @@
+use crate::constant::VAULT_REWARD_SEED;
+use crate::events::UnallocatedWithdrawn;
+use crate::rewards::settle_contribution;
+use crate::state::{Contribution, ErrorCode, Vault, VaultStatus};
+use crate::util::{get_now_timestamp, update_reward_per_token};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+#[derive(Accounts)]
+pub struct WithdrawUnallocated<'info> {
+    // anyone can settle a contribution, the refund always goes to its funder
+    caller: Signer<'info>,
+
+    // funder, receives the rent of the contribution
+    #[account(mut)]
+    funder: AccountInfo<'info>,
+
+    // vault
+    #[account(mut,
+    constraint = vault.status == VaultStatus::Initialized)]
+    vault: Account<'info, Vault>,
+
+    // reward pda account
+    #[account(seeds = [VAULT_REWARD_SEED.as_bytes(), vault.key().as_ref()], bump = vault.reward_bump)]
+    reward: SystemAccount<'info>,
+
+    // reward account of the refunded stream
+    #[account(mut)]
+    reward_account: Box<Account<'info, TokenAccount>>,
+
+    // funder account
+    #[account(mut,
+    constraint = funder_account.mint == reward_account.mint,
+    constraint = funder_account.owner == funder.key())]
+    funder_account: Box<Account<'info, TokenAccount>>,
+
+    // contribution of the funder, closed once refunded
+    #[account(mut,
+    close = funder,
+    has_one = vault,
+    has_one = funder,
+    constraint = contribution.reward_mint == reward_account.mint)]
+    contribution: Account<'info, Contribution>,
+
+    // token program
+    #[account(address = spl_token::id())]
+    token_program: Program<'info, Token>,
+}
+
+// once the funded period of a stream ends, what is not owed to stakers is shared among its funders
+// in proportion to their contributions, the stream can not be funded again until all are settled
+pub fn withdraw_unallocated(ctx: Context<WithdrawUnallocated>) -> ProgramResult {
+    let vault = &mut ctx.accounts.vault;
+    let now = get_now_timestamp()?;
+
+    let index = vault
+        .reward_stream_index(&ctx.accounts.reward_account.key())
+        .ok_or(ErrorCode::RewardStreamDoesNotExist)?;
+    let deadline = vault.reward_streams[index].reward_duration_deadline;
+    if deadline == 0 || now < deadline {
+        return Err(ErrorCode::RewardPeriodNotEnded.into());
+    }
+
+    // settle what the ended period emitted before sharing what is left
+    update_reward_per_token(vault, now)?;
+    let stream = &mut vault.reward_streams[index];
+    let amount = settle_contribution(
+        stream,
+        ctx.accounts.reward_account.amount,
+        ctx.accounts.contribution.amount,
+    )?;
+    let reward_mint = stream.reward_mint;
+
+    let vault_address = vault.key();
+    let seeds = &[
+        VAULT_REWARD_SEED.as_bytes(),
+        vault_address.as_ref(),
+        &[vault.reward_bump],
+    ];
+
+    let cpi_context = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        anchor_spl::token::Transfer {
+            from: ctx.accounts.reward_account.to_account_info(),
+            to: ctx.accounts.funder_account.to_account_info(),
+            authority: ctx.accounts.reward.to_account_info(),
+        },
+    );
+
+    anchor_spl::token::transfer(cpi_context.with_signer(&[&seeds[..]]), amount)?;
+
+    emit!(UnallocatedWithdrawn {
+        vault: vault_address,
+        reward_mint,
+        funder: ctx.accounts.funder.key(),
+        contribution: ctx.accounts.contribution.amount,
+        amount,
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -1,10 +1,15 @@
 mod constant;
+mod custody;
+mod events;
 mod instructions;
+mod metadata;
+mod rewards;
 mod state;
 mod util;
 
 use anchor_lang::prelude::*;
 use instructions::*;
+use state::{CustodyMode, EmissionSchedule, LockTier, Role, StakeMode, VaultConfig, WhitelistType};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
@@ -16,43 +21,187 @@ pub mod x_token_stake {
         reward_bump: u8,
         reward_duration: u64,
         stake_token_count: u32,
+        stake_mode: StakeMode,
+        stake_mint: Pubkey,
+        custody_mode: CustodyMode,
     ) -> ProgramResult {
-        create_vault::create_vault(ctx, reward_bump, reward_duration, stake_token_count)
+        create_vault::create_vault(
+            ctx,
+            reward_bump,
+            reward_duration,
+            stake_token_count,
+            stake_mode,
+            stake_mint,
+            custody_mode,
+        )
     }
 
-    pub fn authorize_funder(ctx: Context<ControlFunder>, funder: Pubkey) -> ProgramResult {
-        control_funder::authorize_funder(ctx, funder)
+    pub fn add_reward_stream(ctx: Context<AddRewardStream>, reward_duration: u64) -> ProgramResult {
+        add_reward_stream::add_reward_stream(ctx, reward_duration)
     }
 
-    pub fn unauthorize_funder(ctx: Context<ControlFunder>, funder: Pubkey) -> ProgramResult {
-        control_funder::unauthorize_funder(ctx, funder)
+    pub fn grant_role(
+        ctx: Context<GrantRole>,
+        role_bump: u8,
+        holder: Pubkey,
+        role: Role,
+        reward_mint: Pubkey,
+    ) -> ProgramResult {
+        control_role::grant_role(ctx, role_bump, holder, role, reward_mint)
+    }
+
+    pub fn revoke_role(ctx: Context<RevokeRole>) -> ProgramResult {
+        control_role::revoke_role(ctx)
+    }
+
+    pub fn pause_vault(ctx: Context<ControlVault>) -> ProgramResult {
+        control_vault::pause_vault(ctx)
+    }
+
+    pub fn resume_vault(ctx: Context<ControlVault>) -> ProgramResult {
+        control_vault::resume_vault(ctx)
+    }
+
+    pub fn set_lock_tiers(
+        ctx: Context<ControlVault>,
+        lock_tiers: Vec<LockTier>,
+        early_unstake_penalty: u16,
+    ) -> ProgramResult {
+        control_vault::set_lock_tiers(ctx, lock_tiers, early_unstake_penalty)
+    }
+
+    pub fn propose_authority(
+        ctx: Context<ControlVault>,
+        pending_authority: Pubkey,
+    ) -> ProgramResult {
+        control_vault::propose_authority(ctx, pending_authority)
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> ProgramResult {
+        control_vault::accept_authority(ctx)
+    }
+
+    pub fn update_vault_config(ctx: Context<ControlVault>, config: VaultConfig) -> ProgramResult {
+        control_vault::update_vault_config(ctx, config)
+    }
+
+    pub fn set_collection(ctx: Context<ControlVault>, collection: Pubkey) -> ProgramResult {
+        control_vault::set_collection(ctx, collection)
+    }
+
+    pub fn set_emission_schedule(
+        ctx: Context<ControlVault>,
+        reward_index: u8,
+        schedule: EmissionSchedule,
+    ) -> ProgramResult {
+        control_vault::set_emission_schedule(ctx, reward_index, schedule)
+    }
+
+    pub fn add_whitelist(
+        ctx: Context<AddWhitelist>,
+        whitelist_bump: u8,
+        address: Pubkey,
+        whitelist_type: WhitelistType,
+    ) -> ProgramResult {
+        control_whitelist::add_whitelist(ctx, whitelist_bump, address, whitelist_type)
+    }
+
+    pub fn remove_whitelist(ctx: Context<RemoveWhitelist>) -> ProgramResult {
+        control_whitelist::remove_whitelist(ctx)
+    }
+
+    pub fn set_mint_weight(
+        ctx: Context<SetMintWeight>,
+        weight_bump: u8,
+        mint: Pubkey,
+        weight: u32,
+    ) -> ProgramResult {
+        control_weight::set_mint_weight(ctx, weight_bump, mint, weight)
+    }
+
+    pub fn remove_mint_weight(ctx: Context<RemoveMintWeight>) -> ProgramResult {
+        control_weight::remove_mint_weight(ctx)
     }
 
-    pub fn fund(ctx: Context<Fund>, amount: u64) -> ProgramResult {
-        fund::fund(ctx, amount)
+    pub fn fund(ctx: Context<Fund>, amount: u64, contribution_bump: u8) -> ProgramResult {
+        fund::fund(ctx, amount, contribution_bump)
+    }
+
+    pub fn withdraw_unallocated(ctx: Context<WithdrawUnallocated>) -> ProgramResult {
+        withdraw_unallocated::withdraw_unallocated(ctx)
     }
 
     pub fn create_user(ctx: Context<CreateUser>, user_bump: u8) -> ProgramResult {
         create_user::create_user(ctx, user_bump)
     }
 
-    pub fn stake(ctx: Context<Stake>) -> ProgramResult {
-        stake::stake(ctx)
+    pub fn stake(ctx: Context<Stake>, receipt_bump: u8, lock_tier: Option<u8>) -> ProgramResult {
+        stake::stake(ctx, receipt_bump, lock_tier)
+    }
+
+    pub fn stake_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeMany<'info>>,
+        lock_tier: Option<u8>,
+    ) -> ProgramResult {
+        stake::stake_many(ctx, lock_tier)
     }
 
     pub fn unstake(ctx: Context<Unstake>, vault_stake_bump: u8) -> ProgramResult {
         unstake::unstake(ctx, vault_stake_bump)
     }
 
-    pub fn claim(ctx: Context<Claim>) -> ProgramResult {
-        claim::claim(ctx)
+    pub fn unstake_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, UnstakeMany<'info>>,
+        vault_stake_bump: u8,
+    ) -> ProgramResult {
+        unstake::unstake_many(ctx, vault_stake_bump)
+    }
+
+    pub fn deposit(ctx: Context<Deposit>, escrow_bump: u8, amount: u64) -> ProgramResult {
+        deposit::deposit(ctx, escrow_bump, amount)
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, escrow_bump: u8, amount: u64) -> ProgramResult {
+        withdraw::withdraw(ctx, escrow_bump, amount)
+    }
+
+    pub fn compound(ctx: Context<Compound>, escrow_bump: u8) -> ProgramResult {
+        compound::compound(ctx, escrow_bump)
+    }
+
+    pub fn emergency_unstake(
+        ctx: Context<EmergencyUnstake>,
+        vault_stake_bump: u8,
+    ) -> ProgramResult {
+        emergency_unstake::emergency_unstake(ctx, vault_stake_bump)
+    }
+
+    pub fn claim<'info>(
+        ctx: Context<'_, '_, '_, 'info, Claim<'info>>,
+        reward_index: Option<u8>,
+    ) -> ProgramResult {
+        claim::claim(ctx, reward_index)
+    }
+
+    pub fn crank_claim<'info>(
+        ctx: Context<'_, '_, '_, 'info, CrankClaim<'info>>,
+        reward_index: Option<u8>,
+    ) -> ProgramResult {
+        claim::crank_claim(ctx, reward_index)
+    }
+
+    pub fn set_reward_recipient(
+        ctx: Context<SetRewardRecipient>,
+        recipient: Pubkey,
+    ) -> ProgramResult {
+        claim::set_reward_recipient(ctx, recipient)
     }
 
     pub fn close_user(ctx: Context<CloseUser>) -> ProgramResult {
         close_user::close_user(ctx)
     }
 
-    pub fn close_vault(ctx: Context<CloseVault>) -> ProgramResult {
+    pub fn close_vault<'info>(ctx: Context<'_, '_, '_, 'info, CloseVault<'info>>) -> ProgramResult {
         close_vault::close_vault(ctx)
     }
 }
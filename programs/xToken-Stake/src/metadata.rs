@@ -0,0 +1,118 @@
+use crate::constant::METADATA_SEED;
+use crate::state::ErrorCode;
+use anchor_lang::prelude::*;
+
+pub mod token_metadata_program {
+    anchor_lang::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+// unread fields only keep the deserialized layout in step with the metadata account
+#[allow(dead_code)]
+#[derive(AnchorDeserialize, Clone, Debug)]
+pub struct Creator {
+    // creator address
+    pub address: Pubkey,
+    // creator signed the metadata
+    pub verified: bool,
+    // royalty share
+    pub share: u8,
+}
+
+#[allow(dead_code)]
+#[derive(AnchorDeserialize, Clone, Debug)]
+pub struct Data {
+    // token name
+    pub name: String,
+    // token symbol
+    pub symbol: String,
+    // token uri
+    pub uri: String,
+    // royalty basis points
+    pub seller_fee_basis_points: u16,
+    // creators
+    pub creators: Option<Vec<Creator>>,
+}
+
+#[derive(AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TokenStandard {
+    NonFungible,
+    FungibleAsset,
+    Fungible,
+    NonFungibleEdition,
+}
+
+#[derive(AnchorDeserialize, Clone, Debug)]
+pub struct Collection {
+    // collection signed the membership
+    pub verified: bool,
+    // collection mint
+    pub key: Pubkey,
+}
+
+// leading part of the Metaplex token metadata account layout
+#[allow(dead_code)]
+#[derive(AnchorDeserialize, Clone, Debug)]
+pub struct Metadata {
+    // account key
+    pub key: u8,
+    // update authority
+    pub update_authority: Pubkey,
+    // mint
+    pub mint: Pubkey,
+    // data
+    pub data: Data,
+    // primary sale happened
+    pub primary_sale_happened: bool,
+    // metadata is mutable
+    pub is_mutable: bool,
+    // edition nonce
+    pub edition_nonce: Option<u8>,
+    // token standard
+    pub token_standard: Option<TokenStandard>,
+    // collection the token belongs to
+    pub collection: Option<Collection>,
+}
+
+impl Metadata {
+    pub fn from_account_info(info: &AccountInfo, mint: &Pubkey) -> Result<Self, ProgramError> {
+        if info.owner != &token_metadata_program::ID {
+            return Err(ErrorCode::InvalidMetadata.into());
+        }
+
+        let (metadata_address, _metadata_bump) = Pubkey::find_program_address(
+            &[
+                METADATA_SEED.as_bytes(),
+                token_metadata_program::ID.as_ref(),
+                mint.as_ref(),
+            ],
+            &token_metadata_program::ID,
+        );
+        if metadata_address != *info.key {
+            return Err(ErrorCode::InvalidMetadata.into());
+        }
+
+        let data = info.try_borrow_data()?;
+        let metadata = Metadata::deserialize(&mut &data[..])
+            .map_err(|_| ProgramError::from(ErrorCode::InvalidMetadata))?;
+        if metadata.mint != *mint {
+            return Err(ErrorCode::InvalidMetadata.into());
+        }
+
+        Ok(metadata)
+    }
+
+    pub fn verified_collection(&self) -> Option<&Pubkey> {
+        self.collection
+            .iter()
+            .find(|collection| collection.verified)
+            .map(|collection| &collection.key)
+    }
+
+    pub fn verified_creators(&self) -> impl Iterator<Item = &Creator> {
+        self.data
+            .creators
+            .iter()
+            .flatten()
+            .filter(|creator| creator.verified)
+    }
+}
@@ -0,0 +1,746 @@
+// reward math of the vault, every timestamp is passed in so it runs off chain as well
+use crate::constant::{CALC_PRECISION, EMISSION_BASE, PENALTY_BASE};
+use crate::state::{EmissionCurve, EmissionSchedule, ErrorCode, RewardStream, Vault, VaultStatus};
+use anchor_lang::prelude::*;
+use std::convert::TryInto;
+
+pub fn last_time_reward_applicable(vault: &Vault, stream: &RewardStream, now: u64) -> u64 {
+    // accrual is frozen while the vault is paused
+    let now = if vault.status == VaultStatus::Paused {
+        std::cmp::min(now, vault.paused_at)
+    } else {
+        now
+    };
+
+    // rewards stop accruing once the funded period is over
+    std::cmp::min(now, stream.reward_duration_deadline)
+}
+
+// emission accumulated by the curve from the start of a period of the given length up to the offset,
+// in EMISSION_BASE per second, a constant curve accumulates EMISSION_BASE every second
+pub fn cumulative_emission(
+    schedule: &EmissionSchedule,
+    period: u64,
+    offset: u64,
+) -> std::result::Result<u128, ProgramError> {
+    let base = EMISSION_BASE as u128;
+    let elapsed = std::cmp::min(offset, period) as u128;
+    let emission = match schedule.curve {
+        EmissionCurve::Constant => base.checked_mul(elapsed).ok_or(ErrorCode::MathOverflow)?,
+        EmissionCurve::LinearDecay => {
+            if elapsed == 0 {
+                return Ok(0);
+            }
+            // integral of a rate falling from base at the start to the end rate at the deadline
+            let decay = base.saturating_sub(schedule.param as u128);
+            let decayed = decay
+                .checked_mul(elapsed)
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_mul(elapsed)
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(
+                    (period as u128)
+                        .checked_mul(2)
+                        .ok_or(ErrorCode::MathOverflow)?,
+                )
+                .ok_or(ErrorCode::MathOverflow)?;
+            base.checked_mul(elapsed)
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_sub(decayed)
+                .ok_or(ErrorCode::MathOverflow)?
+        }
+        EmissionCurve::StepHalving => {
+            let interval = schedule.param as u128;
+            if interval == 0 {
+                return Ok(base.checked_mul(elapsed).ok_or(ErrorCode::MathOverflow)?);
+            }
+            // every full interval emits half of the previous one, nothing is left after 128 halvings
+            let halvings = elapsed
+                .checked_div(interval)
+                .ok_or(ErrorCode::MathOverflow)?;
+            let full = base.checked_mul(interval).ok_or(ErrorCode::MathOverflow)?;
+            let mut emission: u128 = (0..std::cmp::min(halvings, 128) as u32)
+                .map(|halving| full >> halving)
+                .sum();
+            if halvings < 128 {
+                let partial = elapsed
+                    .checked_sub(
+                        halvings
+                            .checked_mul(interval)
+                            .ok_or(ErrorCode::MathOverflow)?,
+                    )
+                    .ok_or(ErrorCode::MathOverflow)?;
+                emission = emission
+                    .checked_add(
+                        base.checked_mul(partial).ok_or(ErrorCode::MathOverflow)? >> halvings,
+                    )
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+            emission
+        }
+        EmissionCurve::Piecewise => {
+            // a checkpoint rate applies until the next checkpoint, nothing is emitted before the first one
+            let checkpoints = &schedule.checkpoints[..schedule.checkpoint_count as usize];
+            let mut emission: u128 = 0;
+            for (index, checkpoint) in checkpoints.iter().enumerate() {
+                let start = checkpoint.offset as u128;
+                if elapsed <= start {
+                    break;
+                }
+                let end = checkpoints
+                    .get(index + 1)
+                    .map_or(elapsed, |next| std::cmp::min(next.offset as u128, elapsed));
+                emission = (checkpoint.rate as u128)
+                    .checked_mul(end.checked_sub(start).ok_or(ErrorCode::MathOverflow)?)
+                    .ok_or(ErrorCode::MathOverflow)?
+                    .checked_add(emission)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+            emission
+        }
+    };
+    Ok(emission)
+}
+
+// emission of the funded period of the stream between two timestamps, in EMISSION_BASE per second
+pub fn emission_weight(
+    stream: &RewardStream,
+    from: u64,
+    to: u64,
+) -> std::result::Result<u128, ProgramError> {
+    let period = stream
+        .reward_duration_deadline
+        .saturating_sub(stream.period_start);
+    let from = from.saturating_sub(stream.period_start);
+    let to = to.saturating_sub(stream.period_start);
+    let emitted_to = cumulative_emission(&stream.schedule, period, to)?;
+    let emitted_from = cumulative_emission(&stream.schedule, period, from)?;
+    Ok(emitted_to.saturating_sub(emitted_from))
+}
+
+// a schedule must emit something over a period and keep its piecewise checkpoints in order
+pub fn verify_emission_schedule(schedule: &EmissionSchedule) -> ProgramResult {
+    let valid = match schedule.curve {
+        EmissionCurve::Constant => true,
+        EmissionCurve::LinearDecay => schedule.param <= EMISSION_BASE as u64,
+        EmissionCurve::StepHalving => schedule.param > 0,
+        EmissionCurve::Piecewise => {
+            let count = schedule.checkpoint_count as usize;
+            count > 0
+                && count <= schedule.checkpoints.len()
+                && schedule.checkpoints[..count]
+                    .windows(2)
+                    .all(|pair| pair[0].offset < pair[1].offset)
+        }
+    };
+    if !valid {
+        return Err(ErrorCode::InvalidEmissionSchedule.into());
+    }
+
+    Ok(())
+}
+
+pub fn earned(
+    balance_staked: u64,
+    reward_per_token: u128,
+    user_reward_per_token_paid: u128,
+    user_reward_pending: u64,
+) -> std::result::Result<u64, ProgramError> {
+    Ok(reward_per_token
+        .checked_sub(user_reward_per_token_paid)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_mul(balance_staked as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(CALC_PRECISION)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_add(user_reward_pending as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?)
+}
+
+// accumulates what the stream emitted since its last update among the staked weight
+pub fn accrue(
+    stream: &mut RewardStream,
+    staked_weight: u64,
+    last_time_reward_applicable: u64,
+) -> ProgramResult {
+    if last_time_reward_applicable <= stream.last_update_time {
+        return Ok(());
+    }
+
+    // nothing is emitted while the vault is empty, those rewards stay in the reward account
+    if staked_weight > 0 {
+        // integrate the emission curve since the last update
+        let weight = emission_weight(stream, stream.last_update_time, last_time_reward_applicable)?;
+        let emitted = stream
+            .reward_rate
+            .checked_mul(weight)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        stream.reward_per_token_stored = emitted
+            .checked_div(staked_weight as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(stream.reward_per_token_stored)
+            .ok_or(ErrorCode::MathOverflow)?;
+        stream.reward_liability = stream
+            .reward_liability
+            .checked_add(emitted)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+    stream.last_update_time = last_time_reward_applicable;
+
+    Ok(())
+}
+
+// starts a new funded period at now, the amount and what the running period has not emitted yet
+// are spread over it along the emission curve, the stream must be accrued first
+pub fn fund_stream(
+    stream: &mut RewardStream,
+    amount: u64,
+    reward_balance: u64,
+    last_time_reward_applicable: u64,
+    now: u64,
+) -> ProgramResult {
+    // rewards of the current period that are not emitted yet roll into the new period
+    let current_value = stream
+        .reward_rate
+        .checked_mul(emission_weight(
+            stream,
+            last_time_reward_applicable,
+            stream.reward_duration_deadline,
+        )?)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    msg!("Current reward overall amount is {}", current_value);
+
+    // the new period starts now and follows the emission curve of the stream
+    stream.period_start = now;
+    stream.last_update_time = now;
+    stream.reward_duration_deadline = now
+        .checked_add(stream.reward_duration)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let period_weight = emission_weight(stream, now, stream.reward_duration_deadline)?;
+    if period_weight == 0 {
+        return Err(ErrorCode::InvalidEmissionSchedule.into());
+    }
+
+    // the rate is per unit of emission weight so it never needs to be scaled up by EMISSION_BASE
+    stream.reward_rate = (amount as u128)
+        .checked_mul(CALC_PRECISION)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_add(current_value)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(period_weight)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    msg!("New reward rate is {}", stream.reward_rate);
+
+    // the reward account must cover what is owed plus everything still to be emitted
+    let required = stream
+        .reward_rate
+        .checked_mul(period_weight)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_add(stream.reward_liability)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let available = (reward_balance as u128)
+        .checked_add(amount as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_mul(CALC_PRECISION)
+        .ok_or(ErrorCode::MathOverflow)?;
+    if available < required {
+        return Err(ErrorCode::InsufficientRewardFunds.into());
+    }
+
+    stream.total_contributed = stream
+        .total_contributed
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+// share of the pending rewards earned by the given part of the user weight that is forfeited
+pub fn early_unstake_penalty(
+    pending: u64,
+    weight: u64,
+    user_staked_weight: u64,
+    early_unstake_penalty: u16,
+) -> std::result::Result<u64, ProgramError> {
+    Ok((pending as u128)
+        .checked_mul(weight as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(user_staked_weight as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_mul(early_unstake_penalty as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(PENALTY_BASE as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?)
+}
+
+// moves a forfeited amount back into the pool, spread over the rest of the running period
+// along its emission curve, once the period is over it stays in the reward account
+pub fn release_penalty(
+    stream: &mut RewardStream,
+    penalty: u64,
+    last_time_reward_applicable: u64,
+) -> ProgramResult {
+    let released = (penalty as u128)
+        .checked_mul(CALC_PRECISION)
+        .ok_or(ErrorCode::MathOverflow)?;
+    stream.reward_liability = stream
+        .reward_liability
+        .checked_sub(released)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let remaining_weight = emission_weight(
+        stream,
+        last_time_reward_applicable,
+        stream.reward_duration_deadline,
+    )?;
+    if remaining_weight > 0 {
+        stream.reward_rate = released
+            .checked_div(remaining_weight)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(stream.reward_rate)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    Ok(())
+}
+
+// what the reward account holds beyond the rewards owed to stakers, rounding the owed amount up
+pub fn unallocated(
+    reward_balance: u64,
+    reward_liability: u128,
+) -> std::result::Result<u64, ProgramError> {
+    let owed = reward_liability
+        .checked_add(CALC_PRECISION - 1)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(CALC_PRECISION)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok((reward_balance as u128)
+        .saturating_sub(owed)
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?)
+}
+
+// settles a contribution to an ended period and returns its refund, every funder receives its
+// share of what is unallocated at the time so rewards forfeited later are shared too, the stream
+// can be funded again once the last contribution is settled
+pub fn settle_contribution(
+    stream: &mut RewardStream,
+    reward_balance: u64,
+    contribution: u64,
+) -> std::result::Result<u64, ProgramError> {
+    let amount = refund_share(
+        unallocated(reward_balance, stream.reward_liability)?,
+        contribution,
+        stream.total_contributed,
+    )?;
+    stream.total_contributed = stream
+        .total_contributed
+        .checked_sub(contribution)
+        .ok_or(ErrorCode::MathOverflow)?;
+    stream.contribution_count = stream
+        .contribution_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    stream.refunding = stream.contribution_count > 0;
+
+    Ok(amount)
+}
+
+// part of the unallocated rewards refunded to a funder, in proportion to its contribution
+pub fn refund_share(
+    unallocated: u64,
+    contribution: u64,
+    total_contributed: u64,
+) -> std::result::Result<u64, ProgramError> {
+    Ok((unallocated as u128)
+        .checked_mul(contribution as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(total_contributed as u128)
+        .unwrap_or(0)
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constant::MAX_EMISSION_CHECKPOINTS;
+    use crate::state::EmissionCheckpoint;
+    use proptest::prelude::*;
+
+    fn schedule(curve: EmissionCurve, param: u64) -> EmissionSchedule {
+        EmissionSchedule {
+            curve,
+            param,
+            ..EmissionSchedule::default()
+        }
+    }
+
+    fn piecewise(checkpoints: &[(u64, u32)]) -> EmissionSchedule {
+        let mut schedule = schedule(EmissionCurve::Piecewise, 0);
+        for (index, (offset, rate)) in checkpoints.iter().enumerate() {
+            schedule.checkpoints[index] = EmissionCheckpoint {
+                offset: *offset,
+                rate: *rate,
+            };
+        }
+        schedule.checkpoint_count = checkpoints.len() as u8;
+        schedule
+    }
+
+    fn stream(reward_duration: u64, schedule: EmissionSchedule) -> RewardStream {
+        RewardStream {
+            reward_duration,
+            schedule,
+            ..RewardStream::default()
+        }
+    }
+
+    #[test]
+    fn constant_curve_spreads_funding_evenly() {
+        let mut stream = stream(100, EmissionSchedule::default());
+        fund_stream(&mut stream, 1_000, 0, 0, 10).unwrap();
+
+        assert_eq!(stream.period_start, 10);
+        assert_eq!(stream.reward_duration_deadline, 110);
+        assert_eq!(
+            stream.reward_rate,
+            1_000 * CALC_PRECISION / (100 * EMISSION_BASE as u128)
+        );
+        assert_eq!(stream.total_contributed, 1_000);
+    }
+
+    #[test]
+    fn linear_decay_to_zero_emits_half_of_constant() {
+        let schedule = schedule(EmissionCurve::LinearDecay, 0);
+        assert_eq!(cumulative_emission(&schedule, 100, 100).unwrap(), 500_000);
+        // the first half of the period emits three quarters
+        assert_eq!(cumulative_emission(&schedule, 100, 50).unwrap(), 375_000);
+    }
+
+    #[test]
+    fn step_halving_halves_every_interval() {
+        let schedule = schedule(EmissionCurve::StepHalving, 10);
+        assert_eq!(cumulative_emission(&schedule, 100, 10).unwrap(), 100_000);
+        assert_eq!(cumulative_emission(&schedule, 100, 20).unwrap(), 150_000);
+        assert_eq!(cumulative_emission(&schedule, 100, 25).unwrap(), 162_500);
+    }
+
+    #[test]
+    fn piecewise_applies_latest_checkpoint() {
+        let schedule = piecewise(&[(10, 10_000), (50, 5_000)]);
+        assert_eq!(cumulative_emission(&schedule, 100, 10).unwrap(), 0);
+        assert_eq!(cumulative_emission(&schedule, 100, 50).unwrap(), 400_000);
+        assert_eq!(cumulative_emission(&schedule, 100, 200).unwrap(), 650_000);
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        let invalid = [
+            schedule(EmissionCurve::LinearDecay, EMISSION_BASE as u64 + 1),
+            schedule(EmissionCurve::StepHalving, 0),
+            piecewise(&[]),
+            piecewise(&[(10, 10_000), (10, 5_000)]),
+        ];
+        for schedule in invalid.iter() {
+            assert_eq!(
+                verify_emission_schedule(schedule),
+                Err(ErrorCode::InvalidEmissionSchedule.into())
+            );
+        }
+    }
+
+    #[test]
+    fn funding_a_silent_period_fails() {
+        let mut stream = stream(100, piecewise(&[(100, 10_000)]));
+        assert_eq!(
+            fund_stream(&mut stream, 1_000, 0, 0, 0),
+            Err(ErrorCode::InvalidEmissionSchedule.into())
+        );
+    }
+
+    #[test]
+    fn funding_must_cover_liability() {
+        let mut owing = stream(100, EmissionSchedule::default());
+        owing.reward_liability = 500 * CALC_PRECISION;
+
+        let mut stream = owing;
+        assert_eq!(
+            fund_stream(&mut stream, 1_000, 400, 0, 0),
+            Err(ErrorCode::InsufficientRewardFunds.into())
+        );
+
+        let mut stream = owing;
+        fund_stream(&mut stream, 1_000, 500, 0, 0).unwrap();
+    }
+
+    #[test]
+    fn accrual_stops_at_deadline_and_claims_stay_within_liability() {
+        let vault = Vault {
+            status: VaultStatus::Initialized,
+            ..Vault::default()
+        };
+        let mut stream = stream(100, EmissionSchedule::default());
+        fund_stream(&mut stream, 1_000, 0, 0, 0).unwrap();
+
+        let applicable = last_time_reward_applicable(&vault, &stream, 250);
+        assert_eq!(applicable, 100);
+        accrue(&mut stream, 4, applicable).unwrap();
+        let reward_per_token = stream.reward_per_token_stored;
+        let applicable = last_time_reward_applicable(&vault, &stream, 300);
+        accrue(&mut stream, 4, applicable).unwrap();
+        assert_eq!(stream.reward_per_token_stored, reward_per_token);
+
+        let claims = earned(1, reward_per_token, 0, 0).unwrap() as u128
+            + earned(3, reward_per_token, 0, 0).unwrap() as u128;
+        assert!(claims * CALC_PRECISION <= stream.reward_liability);
+        assert!(stream.reward_liability <= 1_000 * CALC_PRECISION);
+    }
+
+    #[test]
+    fn unfunded_remainder_rolls_into_next_period() {
+        let mut stream = stream(100, EmissionSchedule::default());
+        fund_stream(&mut stream, 1_000, 0, 0, 0).unwrap();
+        accrue(&mut stream, 1, 50).unwrap();
+        fund_stream(&mut stream, 1_000, 1_000, 50, 50).unwrap();
+
+        assert_eq!(
+            stream.reward_rate,
+            1_500 * CALC_PRECISION / (100 * EMISSION_BASE as u128)
+        );
+        assert_eq!(stream.reward_duration_deadline, 150);
+    }
+
+    #[test]
+    fn penalty_is_released_over_remaining_period() {
+        let mut stream = stream(100, EmissionSchedule::default());
+        fund_stream(&mut stream, 1_000, 0, 0, 0).unwrap();
+        accrue(&mut stream, 1, 50).unwrap();
+
+        let penalty = early_unstake_penalty(500, 1, 2, 5_000).unwrap();
+        assert_eq!(penalty, 125);
+        let rate = stream.reward_rate;
+        let liability = stream.reward_liability;
+        release_penalty(&mut stream, penalty, 50).unwrap();
+        assert_eq!(
+            stream.reward_rate,
+            rate + 125 * CALC_PRECISION / (50 * EMISSION_BASE as u128)
+        );
+        assert_eq!(stream.reward_liability, liability - 125 * CALC_PRECISION);
+    }
+
+    #[test]
+    fn unallocated_rounds_owed_rewards_up() {
+        assert_eq!(unallocated(1_000, 400 * CALC_PRECISION + 1).unwrap(), 599);
+        assert_eq!(refund_share(599, 1, 3).unwrap(), 199);
+        assert_eq!(refund_share(599, 1, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn settling_contributions_shares_late_penalties_and_reopens_the_stream() {
+        let mut stream = stream(100, EmissionSchedule::default());
+        stream.reward_duration_deadline = 100;
+        stream.total_contributed = 300;
+        stream.contribution_count = 2;
+        stream.reward_liability = 100 * CALC_PRECISION;
+
+        // 200 of the 300 unallocated go to the larger contribution
+        assert_eq!(settle_contribution(&mut stream, 400, 200).unwrap(), 200);
+        assert!(stream.refunding);
+        assert_eq!(stream.total_contributed, 100);
+
+        // a penalty forfeited after the first refund ends up with the last funder
+        release_penalty(&mut stream, 40, 100).unwrap();
+        assert_eq!(settle_contribution(&mut stream, 200, 100).unwrap(), 140);
+        assert!(!stream.refunding);
+        assert_eq!(stream.total_contributed, 0);
+        assert_eq!(stream.contribution_count, 0);
+    }
+
+    #[test]
+    fn funding_the_whole_token_supply_does_not_overflow() {
+        let mut stream = stream(10 * 365 * 86_400, EmissionSchedule::default());
+        fund_stream(&mut stream, u64::MAX, 0, 0, 0).unwrap();
+        let deadline = stream.reward_duration_deadline;
+        accrue(&mut stream, 1, deadline).unwrap();
+
+        assert!(stream.reward_liability <= u64::MAX as u128 * CALC_PRECISION);
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        assert_eq!(
+            earned(u64::MAX, u128::MAX, 0, 0),
+            Err(ErrorCode::MathOverflow.into())
+        );
+        assert_eq!(
+            early_unstake_penalty(1, 1, 0, 5_000),
+            Err(ErrorCode::MathOverflow.into())
+        );
+    }
+
+    fn any_schedule() -> impl Strategy<Value = EmissionSchedule> {
+        prop_oneof![
+            Just(EmissionSchedule::default()),
+            (0..=EMISSION_BASE as u64).prop_map(|end| schedule(EmissionCurve::LinearDecay, end)),
+            (1u64..500).prop_map(|interval| schedule(EmissionCurve::StepHalving, interval)),
+            (
+                0u64..100,
+                prop::collection::vec((1u64..500, 0u32..20_000), 1..=MAX_EMISSION_CHECKPOINTS)
+            )
+                .prop_map(|(first, steps)| {
+                    let mut offset = first;
+                    let checkpoints: Vec<(u64, u32)> = steps
+                        .iter()
+                        .map(|(gap, rate)| {
+                            let checkpoint = (offset, *rate);
+                            offset += gap;
+                            checkpoint
+                        })
+                        .collect();
+                    piecewise(&checkpoints)
+                }),
+        ]
+    }
+
+    #[derive(Debug, Clone)]
+    enum Action {
+        Wait(u64),
+        Stake(usize, u64),
+        Unstake(usize, u16),
+        Fund(u64),
+        Claim(usize),
+    }
+
+    fn any_action() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            (1u64..500).prop_map(Action::Wait),
+            (0usize..3, 1u64..1_000).prop_map(|(staker, weight)| Action::Stake(staker, weight)),
+            (0usize..3, 0..=PENALTY_BASE)
+                .prop_map(|(staker, penalty)| Action::Unstake(staker, penalty)),
+            // small amounts and amounts near the token supply limit, at most 60 fit in a u64
+            prop_oneof![1u64..1_000_000_000, 1u64..u64::MAX / 64].prop_map(Action::Fund),
+            (0usize..3).prop_map(Action::Claim),
+        ]
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Staker {
+        weight: u64,
+        reward_per_token_paid: u128,
+        pending: u64,
+    }
+
+    // moves what the staker earned into pending, the same way update_rewards does
+    fn settle(stream: &RewardStream, staker: &mut Staker) {
+        staker.pending = earned(
+            staker.weight,
+            stream.reward_per_token_stored,
+            staker.reward_per_token_paid,
+            staker.pending,
+        )
+        .unwrap();
+        staker.reward_per_token_paid = stream.reward_per_token_stored;
+    }
+
+    proptest! {
+        #[test]
+        fn cumulative_emission_is_monotonic(
+            schedule in any_schedule(),
+            period in 1u64..1_000_000,
+            from in 0u64..1_100_000,
+            step in 0u64..100_000,
+        ) {
+            let at_from = cumulative_emission(&schedule, period, from).unwrap();
+            let at_to = cumulative_emission(&schedule, period, from + step).unwrap();
+            prop_assert!(at_from <= at_to);
+        }
+
+        #[test]
+        fn paid_rewards_never_exceed_funding(
+            schedule in any_schedule(),
+            reward_duration in 1u64..2_000,
+            actions in prop::collection::vec(any_action(), 1..60),
+        ) {
+            let mut stream = stream(reward_duration, schedule);
+            let mut stakers = [Staker::default(); 3];
+            let mut now = 1u64;
+            let mut staked_weight = 0u64;
+            let mut balance = 0u64;
+            let mut funded = 0u64;
+            let mut claimed = 0u64;
+
+            for action in actions {
+                let applicable = std::cmp::min(now, stream.reward_duration_deadline);
+                let reward_per_token = stream.reward_per_token_stored;
+                accrue(&mut stream, staked_weight, applicable).unwrap();
+                prop_assert!(stream.reward_per_token_stored >= reward_per_token);
+
+                match action {
+                    Action::Wait(seconds) => now += seconds,
+                    Action::Stake(index, weight) => {
+                        settle(&stream, &mut stakers[index]);
+                        stakers[index].weight += weight;
+                        staked_weight += weight;
+                    }
+                    Action::Unstake(index, penalty) => {
+                        let staker = &mut stakers[index];
+                        settle(&stream, staker);
+                        if staker.weight == 0 {
+                            continue;
+                        }
+                        let forfeited =
+                            early_unstake_penalty(staker.pending, staker.weight, staker.weight, penalty)
+                                .unwrap();
+                        staker.pending -= forfeited;
+                        release_penalty(&mut stream, forfeited, applicable).unwrap();
+                        staked_weight -= staker.weight;
+                        staker.weight = 0;
+                    }
+                    Action::Fund(amount) => {
+                        // a failed funding reverts the whole transaction, only a curve that emits
+                        // nothing over the period may reject it
+                        let mut funded_stream = stream;
+                        match fund_stream(&mut funded_stream, amount, balance, applicable, now) {
+                            Ok(()) => {
+                                stream = funded_stream;
+                                balance += amount;
+                                funded += amount;
+                            }
+                            Err(error) => {
+                                prop_assert_eq!(error, ErrorCode::InvalidEmissionSchedule.into())
+                            }
+                        }
+                    }
+                    Action::Claim(index) => {
+                        let staker = &mut stakers[index];
+                        settle(&stream, staker);
+                        prop_assert!(staker.pending <= balance);
+                        balance -= staker.pending;
+                        claimed += staker.pending;
+                        stream.reward_liability -= staker.pending as u128 * CALC_PRECISION;
+                        staker.pending = 0;
+                    }
+                }
+            }
+
+            // once the period is over every staker settles what is left
+            let deadline = stream.reward_duration_deadline;
+            accrue(&mut stream, staked_weight, deadline).unwrap();
+            let mut owed = 0u128;
+            for staker in stakers.iter_mut() {
+                settle(&stream, staker);
+                owed += staker.pending as u128;
+            }
+            prop_assert!(owed * CALC_PRECISION <= stream.reward_liability);
+            prop_assert!(claimed as u128 + owed <= funded as u128);
+            prop_assert!(owed <= balance as u128);
+        }
+    }
+}
@@ -1,18 +1,133 @@
+use crate::constant::{
+    MAX_EMISSION_CHECKPOINTS, MAX_LOCK_TIERS, MAX_REWARD_STREAMS, MULTIPLIER_BASE,
+};
 use anchor_lang::prelude::*;
 
-#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Default)]
 pub enum VaultStatus {
+    #[default]
     None,
     Initialized,
     Paused,
 }
 
-impl Default for VaultStatus {
-    fn default() -> Self {
-        Self::None
-    }
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StakeMode {
+    // every staked token account weighs one, for nfts
+    #[default]
+    Nft,
+    // deposited amount of the stake mint is the weight, for fungible tokens
+    Fungible,
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CustodyMode {
+    // the vault pda becomes the owner of the staked token account
+    #[default]
+    SetAuthority,
+    // the staked token moves into a token account owned by the vault pda
+    Escrow,
+    // the staked token stays in the wallet, delegated to the vault pda and frozen,
+    // the freeze authority of the mint must be the vault freeze pda
+    Freeze,
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum WhitelistType {
+    Mint,
+    Creator,
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmissionCurve {
+    // the same rate over the whole funded period
+    #[default]
+    Constant,
+    // the rate decays linearly from full to the end rate at the deadline
+    LinearDecay,
+    // the rate halves every interval
+    StepHalving,
+    // the rate of the latest checkpoint applies
+    Piecewise,
+}
+
+pub const EMISSION_CHECKPOINT_SIZE: usize = 8 + 4;
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EmissionCheckpoint {
+    // seconds from the start of the funded period
+    pub offset: u64,
+    // rate from the offset on, in basis points of EMISSION_BASE
+    pub rate: u32,
+}
+
+pub const EMISSION_SCHEDULE_SIZE: usize =
+    1 + 8 + 1 + EMISSION_CHECKPOINT_SIZE * MAX_EMISSION_CHECKPOINTS;
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EmissionSchedule {
+    // shape of the emission over the funded period
+    pub curve: EmissionCurve,
+    // end rate in basis points of EMISSION_BASE for linear decay, interval in seconds for step halving
+    pub param: u64,
+    // number of checkpoints used by the piecewise curve
+    pub checkpoint_count: u8,
+    // piecewise checkpoints ordered by offset
+    pub checkpoints: [EmissionCheckpoint; MAX_EMISSION_CHECKPOINTS],
+}
+
+pub const REWARD_STREAM_SIZE: usize =
+    32 + 32 + 8 + 8 + 16 + 16 + 8 + 16 + 8 + 4 + 1 + 8 + EMISSION_SCHEDULE_SIZE;
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct RewardStream {
+    // reward token
+    pub reward_mint: Pubkey,
+    // reward token account
+    pub reward_mint_account: Pubkey,
+    // reward duration
+    pub reward_duration: u64,
+    // reward duration deadline
+    pub reward_duration_deadline: u64,
+    // reward for the whole vault per unit of emission weight, scaled by CALC_PRECISION,
+    // a second at the full rate of the emission curve weighs EMISSION_BASE
+    pub reward_rate: u128,
+    // accumulated reward per staked token
+    pub reward_per_token_stored: u128,
+    // last time reward per token was accumulated
+    pub last_update_time: u64,
+    // rewards emitted but not claimed yet, scaled by CALC_PRECISION
+    pub reward_liability: u128,
+    // total amount funded by the unsettled contributions, funders are refunded in proportion
+    // to their share of it
+    pub total_contributed: u64,
+    // number of unsettled contributions, the vault can not be closed before all are settled
+    pub contribution_count: u32,
+    // funders are being refunded, the stream can be funded again once all are settled
+    pub refunding: bool,
+    // start of the funded period, the emission curve is evaluated from it
+    pub period_start: u64,
+    // emission curve of the funded period
+    pub schedule: EmissionSchedule,
+}
+
+pub const LOCK_TIER_SIZE: usize = 8 + 4;
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct LockTier {
+    // lock duration in seconds
+    pub duration: u64,
+    // reward multiplier in basis points of MULTIPLIER_BASE
+    pub multiplier: u32,
 }
-pub const VAULT_SIZE: usize = 8 + 32 + 4 + 1 + 32 + 32 + 8 + 32 + 8 + 8 + 16 + 4 + 4 + 4 + 32 * 5;
+
+pub const VAULT_SIZE: usize = (8 + 32 + 1 + 1 + 1 + 32 + 1 + 1)
+    + REWARD_STREAM_SIZE * MAX_REWARD_STREAMS
+    + (8 + 4 + 4 + 4 + 8 + 4 + 4 + 4)
+    + (1 + LOCK_TIER_SIZE * MAX_LOCK_TIERS + 2)
+    + 4
+    + 32
+    + 32;
 pub const VAULT_COUNT: usize = 19029122;
 
 #[account]
@@ -24,45 +139,205 @@ pub struct Vault {
     pub status: VaultStatus,
     // escrow
     pub reward_bump: u8,
-    // reward token
-    pub reward_mint: Pubkey,
-    // reward token account
-    pub reward_mint_account: Pubkey,
-    // reward duration
-    pub reward_duration: u64,
-    // reward duration deadline
-    pub reward_duration_deadline: u64,
-    // reward rate
-    pub reward_rate: u128,
+    // kind of staked tokens
+    pub stake_mode: StakeMode,
+    // stake token of a fungible vault
+    pub stake_mint: Pubkey,
+    // how staked nfts are held
+    pub custody_mode: CustodyMode,
+    // number of reward streams in use
+    pub reward_stream_count: u8,
+    // reward streams, only the first reward_stream_count are in use
+    pub reward_streams: [RewardStream; MAX_REWARD_STREAMS],
+    // time the vault was paused
+    pub paused_at: u64,
     // number of tokens
     pub stake_token_count: u32,
+    // number of tokens a user can stake, unlimited when zero
+    pub max_stake_per_user: u32,
     // number of tokens staked
     pub staked_count: u32,
+    // total weight staked, rewards are shared in proportion to it
+    pub staked_weight: u64,
     // number of users
     pub user_count: u32,
-    // array of funders address
-    pub funders: [Pubkey; 5],
+    // number of whitelisted mints
+    pub whitelisted_mints: u32,
+    // number of whitelisted creators
+    pub whitelisted_creators: u32,
+    // number of lock tiers in use
+    pub lock_tier_count: u8,
+    // lock tiers a stake can choose from, only the first lock_tier_count are in use
+    pub lock_tiers: [LockTier; MAX_LOCK_TIERS],
+    // share of pending rewards forfeited by an early unstake in basis points of PENALTY_BASE,
+    // early unstakes are rejected when zero
+    pub early_unstake_penalty: u16,
+    // number of mints with a weight, other mints weigh MULTIPLIER_BASE
+    pub weighted_mints: u32,
+    // verified collection every staked nft must belong to, any token is accepted when default
+    pub collection: Pubkey,
+    // proposed authority, becomes the authority once it accepts, none when default
+    pub pending_authority: Pubkey,
+}
+
+// vault tunables to change, a none field is kept
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct VaultConfig {
+    // reward stream whose duration changes
+    pub reward_index: u8,
+    // reward duration of the next funding period of the stream
+    pub reward_duration: Option<u64>,
+    // number of tokens, can not be below the staked count
+    pub stake_token_count: Option<u32>,
+    // number of tokens a user can stake, unlimited when zero
+    pub max_stake_per_user: Option<u32>,
+}
+
+impl Vault {
+    pub fn reward_stream_index(&self, reward_mint_account: &Pubkey) -> Option<usize> {
+        self.reward_streams[..self.reward_stream_count as usize]
+            .iter()
+            .position(|stream| stream.reward_mint_account == *reward_mint_account)
+    }
+
+    pub fn has_reward_mint(&self, reward_mint: &Pubkey) -> bool {
+        self.reward_streams[..self.reward_stream_count as usize]
+            .iter()
+            .any(|stream| stream.reward_mint == *reward_mint)
+    }
+
+    // a stake without a lock tier can be unstaked any time and earns the base multiplier
+    pub fn lock_tier(&self, lock_tier: Option<u8>) -> std::result::Result<LockTier, ProgramError> {
+        match lock_tier {
+            None => Ok(LockTier {
+                duration: 0,
+                multiplier: MULTIPLIER_BASE,
+            }),
+            Some(index) if index < self.lock_tier_count => Ok(self.lock_tiers[index as usize]),
+            Some(_) => Err(ErrorCode::InvalidLockTier.into()),
+        }
+    }
 }
 
-pub const USER_SIZE: usize = 8 + 32 + 32 + 8 + 8 + 4 + 32 * 300 + 8;
+pub const VAULT_WHITELIST_SIZE: usize = 8 + 32 + 32 + 1;
 
 #[account]
-#[derive(Default)]
-pub struct User {
+pub struct VaultWhitelist {
     // vault
     pub vault: Pubkey,
-    // user pub key
-    pub key: Pubkey,
+    // whitelisted mint or creator address
+    pub address: Pubkey,
+    // kind of whitelisted address
+    pub whitelist_type: WhitelistType,
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct UserReward {
     // total amount of reward claimed
     pub reward_earned_claimed: u64,
     // total amount of reward pending
     pub reward_earned_pending: u64,
+    // stream reward per token at the last update
+    pub reward_per_token_paid: u128,
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    // every vault setting except roles, the authority and closing the vault
+    Admin,
+    // fund the reward stream the role is granted for
+    Funder,
+    // pause and resume the vault
+    Pauser,
+    // add and remove whitelist entries
+    WhitelistManager,
+}
+
+pub const VAULT_ROLE_SIZE: usize = 8 + 32 + 32 + 1 + 32;
+
+#[account]
+pub struct VaultRole {
+    // vault
+    pub vault: Pubkey,
+    // role holder
+    pub holder: Pubkey,
+    // granted role
+    pub role: Role,
+    // reward token of the stream a funder role is scoped to, default for other roles
+    pub reward_mint: Pubkey,
+}
+
+pub const CONTRIBUTION_SIZE: usize = 8 + 32 + 32 + 32 + 8;
+
+#[account]
+pub struct Contribution {
+    // vault
+    pub vault: Pubkey,
+    // reward token of the funded stream
+    pub reward_mint: Pubkey,
+    // funder
+    pub funder: Pubkey,
+    // total amount funded
+    pub amount: u64,
+}
+
+pub const MINT_WEIGHT_SIZE: usize = 8 + 32 + 32 + 4;
+
+#[account]
+pub struct MintWeight {
+    // vault
+    pub vault: Pubkey,
+    // weighted mint
+    pub mint: Pubkey,
+    // weight in basis points of MULTIPLIER_BASE
+    pub weight: u32,
+}
+
+pub const USER_SIZE: usize = 8 + 32 + 32 + (8 + 8 + 16) * MAX_REWARD_STREAMS + 4 + 8 + 8 + 32 + 8;
+
+#[account]
+#[derive(Default)]
+pub struct User {
+    // vault
+    pub vault: Pubkey,
+    // user pub key
+    pub key: Pubkey,
+    // rewards per stream, in the order of the vault reward streams
+    pub rewards: [UserReward; MAX_REWARD_STREAMS],
     // number of mints staked
     pub mint_staked_count: u32,
-    // mint_staked
-    pub mint_accounts: Vec<Pubkey>,
+    // weight staked
+    pub staked_weight: u64,
     // last_stake_time
     pub last_stake_time: u64,
+    // owner of the accounts crank_claim pays the rewards to, none when default
+    pub reward_recipient: Pubkey,
+    // latest lock end of the staked tokens, rewards can not be claimed before it so an early
+    // unstake can not dodge its penalty, reset once nothing is staked
+    pub lock_end_time: u64,
+}
+
+pub const STAKE_RECEIPT_SIZE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 4 + 8 + 8;
+
+#[account]
+#[derive(Default)]
+pub struct StakeReceipt {
+    // vault
+    pub vault: Pubkey,
+    // staker pub key
+    pub owner: Pubkey,
+    // staked mint
+    pub mint: Pubkey,
+    // staked token account
+    pub stake_account: Pubkey,
+    // stake time
+    pub stake_time: u64,
+    // reward multiplier in basis points of MULTIPLIER_BASE, the staked weight of the token
+    pub multiplier: u32,
+    // time the stake can be unstaked without penalty
+    pub lock_end_time: u64,
+    // amount of the staked token account, the part of a shared escrow that belongs to the stake
+    pub amount: u64,
 }
 
 #[error]
@@ -93,4 +368,74 @@ pub enum ErrorCode {
     StakeExist,
     #[msg("Earned pending esists")]
     EarnedPendingExist,
+    #[msg("Stake token is not whitelisted.")]
+    NotWhitelisted,
+    #[msg("Invalid metadata account.")]
+    InvalidMetadata,
+    #[msg("Reward account can not cover the reward liability.")]
+    InsufficientRewardFunds,
+    #[msg("Vault is not paused.")]
+    VaultNotPaused,
+    #[msg("Invalid stake account.")]
+    InvalidStakeAccount,
+    #[msg("No stake accounts given.")]
+    NoStakeAccounts,
+    #[msg("Reward streams are full.")]
+    RewardStreamsFull,
+    #[msg("Reward stream already exists.")]
+    RewardStreamExists,
+    #[msg("Reward stream does not exist.")]
+    RewardStreamDoesNotExist,
+    #[msg("Instruction does not match the vault stake mode.")]
+    InvalidStakeMode,
+    #[msg("Amount exceeds the staked amount.")]
+    InsufficientStake,
+    #[msg("Amount can not be zero.")]
+    ZeroAmount,
+    #[msg("Too many lock tiers.")]
+    TooManyLockTiers,
+    #[msg("Invalid lock tier.")]
+    InvalidLockTier,
+    #[msg("Invalid early unstake penalty.")]
+    InvalidPenalty,
+    #[msg("Stake is still locked.")]
+    StakeLocked,
+    #[msg("Invalid mint weight.")]
+    InvalidWeight,
+    #[msg("Invalid mint weight account.")]
+    InvalidWeightAccount,
+    #[msg("Invalid escrow account.")]
+    InvalidEscrowAccount,
+    #[msg("Mint freeze authority is not the vault freeze pda.")]
+    InvalidFreezeAuthority,
+    #[msg("Reward stream is refunding its funders.")]
+    RewardStreamRefunding,
+    #[msg("Reward period has not ended.")]
+    RewardPeriodNotEnded,
+    #[msg("Signer does not hold the required role.")]
+    MissingRole,
+    #[msg("Stake token count can not be below the staked count.")]
+    InvalidStakeTokenCount,
+    #[msg("Max stake count per user reached.")]
+    MaxUserStakeCountReached,
+    #[msg("Stake token is not an nft.")]
+    NotNft,
+    #[msg("Stake token is not a verified member of the vault collection.")]
+    InvalidCollection,
+    #[msg("Invalid emission schedule.")]
+    InvalidEmissionSchedule,
+    #[msg("Math operation overflowed.")]
+    MathOverflow,
+    #[msg("Clock timestamp is invalid.")]
+    InvalidTimestamp,
+    #[msg("User has no reward recipient.")]
+    NoRewardRecipient,
+    #[msg("Reward account is not the associated token account of the recipient.")]
+    InvalidRewardAccount,
+    #[msg("Role reward mint must be a reward stream for funders and default otherwise.")]
+    InvalidRoleRewardMint,
+    #[msg("Reward stream has unsettled contributions.")]
+    ContributionsNotSettled,
+    #[msg("Rewards can not be claimed before the staked tokens unlock.")]
+    RewardsLocked,
 }
@@ -1,51 +1,308 @@
-use crate::constant::CALC_PRECISION;
-use crate::state::{User, Vault};
-use anchor_lang::{prelude::*, solana_program::clock};
+use crate::constant::{
+    MAX_REWARD_STREAMS, MULTIPLIER_BASE, VAULT_WEIGHT_SEED, VAULT_WHITELIST_SEED,
+};
+use crate::metadata::Metadata;
+use crate::rewards::{
+    accrue, early_unstake_penalty, earned, last_time_reward_applicable, release_penalty,
+};
+use crate::state::{
+    ErrorCode, MintWeight, Role, User, Vault, VaultRole, VaultWhitelist, WhitelistType,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{clock, program::invoke, program::invoke_signed, system_instruction},
+};
+use anchor_spl::token::Mint;
 use std::convert::TryInto;
 
-pub fn get_now_timestamp() -> u64 {
-    return clock::Clock::get()
-        .unwrap()
+pub fn get_now_timestamp() -> std::result::Result<u64, ProgramError> {
+    clock::Clock::get()?
         .unix_timestamp
         .try_into()
-        .unwrap();
-}
-
-pub fn earned(
-    elapsed_time: u64,
-    balance_staked: u32,
-    reward_rate_per_token: u128,
-    user_reward_per_token_pending: u64,
-) -> u64 {
-    let earned = (reward_rate_per_token as u128)
-        .checked_div(CALC_PRECISION)
-        .unwrap()
-        .checked_mul(balance_staked as u128)
-        .unwrap()
-        .checked_mul(elapsed_time as u128)
-        .unwrap()
-        .checked_add(user_reward_per_token_pending as u128)
-        .unwrap()
-        .try_into()
-        .unwrap();
-    return earned;
+        .map_err(|_| ErrorCode::InvalidTimestamp.into())
+}
+
+pub fn update_reward_per_token(vault: &mut Account<Vault>, now: u64) -> ProgramResult {
+    for index in 0..vault.reward_stream_count as usize {
+        let last_time_reward_applicable =
+            last_time_reward_applicable(vault, &vault.reward_streams[index], now);
+        let staked_weight = vault.staked_weight;
+        accrue(
+            &mut vault.reward_streams[index],
+            staked_weight,
+            last_time_reward_applicable,
+        )?;
+    }
+
+    Ok(())
 }
 
 pub fn update_rewards(vault: &mut Account<Vault>, user: &mut Account<User>) -> ProgramResult {
-    let now = get_now_timestamp();
+    let now = get_now_timestamp()?;
 
-    // calculate time elapsed since last update
-    let time_diff = std::cmp::max(now - user.last_stake_time, 0 as u64);
+    // accumulate vault reward per token up to now
+    update_reward_per_token(vault, now)?;
 
-    // update user reward to pass it to pending reward
-    user.reward_earned_pending = earned(
-        time_diff,
-        user.mint_staked_count,
-        vault.reward_rate,
-        user.reward_earned_pending,
-    );
+    // update user rewards to pass them to pending rewards
+    let balance_staked = user.staked_weight;
+    for index in 0..vault.reward_stream_count as usize {
+        let stream = &vault.reward_streams[index];
+        let reward = &mut user.rewards[index];
+        reward.reward_earned_pending = earned(
+            balance_staked,
+            stream.reward_per_token_stored,
+            reward.reward_per_token_paid,
+            reward.reward_earned_pending,
+        )?;
+        reward.reward_per_token_paid = stream.reward_per_token_stored;
+    }
     // update time in user account
     user.last_stake_time = now;
 
     Ok(())
 }
+
+// moves the penalty share of the pending rewards earned by the given weight back into the reward pool,
+// spread over the rest of the running reward period along its emission curve, rewards must be updated first
+pub fn apply_early_unstake_penalty(
+    vault: &mut Account<Vault>,
+    user: &mut Account<User>,
+    weight: u64,
+    now: u64,
+) -> std::result::Result<[u64; MAX_REWARD_STREAMS], ProgramError> {
+    let mut penalties = [0u64; MAX_REWARD_STREAMS];
+    let user_staked_weight = user.staked_weight;
+    let count = vault.reward_stream_count as usize;
+    for (index, forfeited) in penalties.iter_mut().enumerate().take(count) {
+        let last_time_reward_applicable =
+            last_time_reward_applicable(vault, &vault.reward_streams[index], now);
+        let reward = &mut user.rewards[index];
+        let penalty = early_unstake_penalty(
+            reward.reward_earned_pending,
+            weight,
+            user_staked_weight,
+            vault.early_unstake_penalty,
+        )?;
+        if penalty == 0 {
+            continue;
+        }
+
+        reward.reward_earned_pending = reward
+            .reward_earned_pending
+            .checked_sub(penalty)
+            .ok_or(ErrorCode::MathOverflow)?;
+        release_penalty(
+            &mut vault.reward_streams[index],
+            penalty,
+            last_time_reward_applicable,
+        )?;
+        *forfeited = penalty;
+    }
+    Ok(penalties)
+}
+
+// the vault authority holds every role, any other signer proves one of the roles
+// with its role entry passed as the first remaining account
+pub fn verify_role(
+    vault: &Account<Vault>,
+    signer: &Pubkey,
+    roles: &[Role],
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if *signer == vault.authority {
+        return Ok(());
+    }
+
+    let role_info = accounts.first().ok_or(ErrorCode::MissingRole)?;
+    let vault_role = Account::<VaultRole>::try_from(role_info)?;
+    if vault_role.vault != vault.key()
+        || vault_role.holder != *signer
+        || !roles.contains(&vault_role.role)
+    {
+        return Err(ErrorCode::MissingRole.into());
+    }
+
+    Ok(())
+}
+
+// the authority and admins fund every stream, a funder only the stream its role is scoped to
+pub fn verify_funder(
+    vault: &Account<Vault>,
+    signer: &Pubkey,
+    reward_mint: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if *signer == vault.authority {
+        return Ok(());
+    }
+
+    let role_info = accounts.first().ok_or(ErrorCode::MissingRole)?;
+    let vault_role = Account::<VaultRole>::try_from(role_info)?;
+    let allowed = match vault_role.role {
+        Role::Admin => true,
+        Role::Funder => vault_role.reward_mint == *reward_mint,
+        _ => false,
+    };
+    if vault_role.vault != vault.key() || vault_role.holder != *signer || !allowed {
+        return Err(ErrorCode::MissingRole.into());
+    }
+
+    Ok(())
+}
+
+pub fn verify_whitelist(
+    program_id: &Pubkey,
+    vault: &Account<Vault>,
+    mint: &Pubkey,
+    proofs: &mut std::slice::Iter<AccountInfo>,
+) -> ProgramResult {
+    // vault without whitelist accepts any token
+    if vault.whitelisted_mints == 0 && vault.whitelisted_creators == 0 {
+        return Ok(());
+    }
+
+    let proof = proofs.next().ok_or(ErrorCode::NotWhitelisted)?;
+    let whitelist = Account::<VaultWhitelist>::try_from(proof)?;
+    if whitelist.vault != vault.key() {
+        return Err(ErrorCode::NotWhitelisted.into());
+    }
+
+    // a mint proof is enough, a creator proof needs the metadata listing that creator as verified
+    let address = match whitelist.whitelist_type {
+        WhitelistType::Mint => *mint,
+        WhitelistType::Creator => {
+            let metadata_info = proofs.next().ok_or(ErrorCode::InvalidMetadata)?;
+            let metadata = Metadata::from_account_info(metadata_info, mint)?;
+            if !metadata
+                .verified_creators()
+                .any(|creator| creator.address == whitelist.address)
+            {
+                return Err(ErrorCode::NotWhitelisted.into());
+            }
+            whitelist.address
+        }
+    };
+
+    let (whitelist_address, _whitelist_bump) = Pubkey::find_program_address(
+        &[
+            VAULT_WHITELIST_SEED.as_bytes(),
+            vault.key().as_ref(),
+            address.as_ref(),
+        ],
+        program_id,
+    );
+    if whitelist_address != *proof.key || whitelist.address != address {
+        return Err(ErrorCode::NotWhitelisted.into());
+    }
+
+    Ok(())
+}
+
+// a vault with a collection needs the mint and metadata of every staked token,
+// the token must be an nft verified as a member of the collection
+pub fn verify_collection(
+    vault: &Account<Vault>,
+    mint: &Pubkey,
+    accounts: &mut std::slice::Iter<AccountInfo>,
+) -> ProgramResult {
+    if vault.collection == Pubkey::default() {
+        return Ok(());
+    }
+
+    let mint_info = accounts.next().ok_or(ErrorCode::NotNft)?;
+    if *mint_info.key != *mint {
+        return Err(ErrorCode::NotNft.into());
+    }
+    let mint_data = Account::<Mint>::try_from(mint_info)?;
+    if mint_data.supply != 1 || mint_data.decimals != 0 {
+        return Err(ErrorCode::NotNft.into());
+    }
+
+    let metadata_info = accounts.next().ok_or(ErrorCode::InvalidMetadata)?;
+    let metadata = Metadata::from_account_info(metadata_info, mint)?;
+    if metadata.verified_collection() != Some(&vault.collection) {
+        return Err(ErrorCode::InvalidCollection.into());
+    }
+
+    Ok(())
+}
+
+// a vault with weighted mints needs the weight pda of every staked mint, an empty one means MULTIPLIER_BASE
+pub fn mint_weight(
+    program_id: &Pubkey,
+    vault: &Account<Vault>,
+    mint: &Pubkey,
+    accounts: &mut std::slice::Iter<AccountInfo>,
+) -> std::result::Result<u32, ProgramError> {
+    if vault.weighted_mints == 0 {
+        return Ok(MULTIPLIER_BASE);
+    }
+
+    let weight_info = accounts.next().ok_or(ErrorCode::InvalidWeightAccount)?;
+    let (weight_address, _weight_bump) = Pubkey::find_program_address(
+        &[
+            VAULT_WEIGHT_SEED.as_bytes(),
+            vault.key().as_ref(),
+            mint.as_ref(),
+        ],
+        program_id,
+    );
+    if weight_address != *weight_info.key {
+        return Err(ErrorCode::InvalidWeightAccount.into());
+    }
+
+    if weight_info.owner != program_id {
+        return Ok(MULTIPLIER_BASE);
+    }
+
+    let mint_weight = Account::<MintWeight>::try_from(weight_info)?;
+    Ok(mint_weight.weight)
+}
+
+// creates a program owned pda the same way anchor `init` does, for accounts passed in remaining accounts
+pub fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    program_id: &Pubkey,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = Rent::get()?;
+    let current_lamports = account.lamports();
+    if current_lamports == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                rent.minimum_balance(space),
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+    } else {
+        let required_lamports = rent
+            .minimum_balance(space)
+            .max(1)
+            .saturating_sub(current_lamports);
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, required_lamports),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account.key, space as u64),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, program_id),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+    }
+    Ok(())
+}
@@ -2,12 +2,68 @@ import * as anchor from "@project-serum/anchor";
 import { Program } from "@project-serum/anchor";
 import { XTokenStake } from "../../target/types/x_token_stake";
 import { Mint } from "./mint";
-import { Vault } from "./vault";
+import { CustodyMode, Role, Vault } from "./vault";
 import { Keypair, PublicKey } from "@solana/web3.js";
 import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
 
 const VAULT_REWARD_SEED = "x_token_vault_reward";
 const VAULT_USER_SEED = "x_token_vault_user";
+const VAULT_WHITELIST_SEED = "x_token_vault_whitelist";
+const VAULT_RECEIPT_SEED = "x_token_vault_receipt";
+const VAULT_ESCROW_SEED = "x_token_vault_escrow";
+const VAULT_WEIGHT_SEED = "x_token_vault_weight";
+const VAULT_FREEZE_SEED = "x_token_vault_freeze";
+const VAULT_ROLE_SEED = "x_token_vault_role";
+const VAULT_CONTRIBUTION_SEED = "x_token_vault_contribution";
+const ROLES = ["admin", "funder", "pauser", "whitelistManager"];
+
+async function getFreezeAddress(
+  vault: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  return await PublicKey.findProgramAddress(
+    [Buffer.from(VAULT_FREEZE_SEED), vault.toBuffer()],
+    program.programId
+  );
+}
+
+async function getRoleAddress(
+  vault: PublicKey,
+  holder: PublicKey,
+  role: Role,
+  rewardMint: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  // the role seed is the variant index
+  const index = ROLES.indexOf(Object.keys(role)[0]);
+  return await PublicKey.findProgramAddress(
+    [
+      Buffer.from(VAULT_ROLE_SEED),
+      vault.toBuffer(),
+      holder.toBuffer(),
+      Buffer.from([index]),
+      rewardMint.toBuffer(),
+    ],
+    program.programId
+  );
+}
+
+async function getContributionAddress(
+  vault: PublicKey,
+  rewardMint: PublicKey,
+  funder: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  return await PublicKey.findProgramAddress(
+    [
+      Buffer.from(VAULT_CONTRIBUTION_SEED),
+      vault.toBuffer(),
+      rewardMint.toBuffer(),
+      funder.toBuffer(),
+    ],
+    program.programId
+  );
+}
 
 function toPublicKey<T extends PublicKey | Keypair>(val: T): PublicKey {
   if ("publicKey" in val) {
@@ -38,6 +94,49 @@ async function getUserAddress(
   );
 }
 
+async function getWhitelistAddress(
+  vault: PublicKey,
+  address: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  return await PublicKey.findProgramAddress(
+    [Buffer.from(VAULT_WHITELIST_SEED), vault.toBuffer(), address.toBuffer()],
+    program.programId
+  );
+}
+
+async function getReceiptAddress(
+  vault: PublicKey,
+  stakeAccount: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  return await PublicKey.findProgramAddress(
+    [Buffer.from(VAULT_RECEIPT_SEED), vault.toBuffer(), stakeAccount.toBuffer()],
+    program.programId
+  );
+}
+
+async function getWeightAddress(
+  vault: PublicKey,
+  mint: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  return await PublicKey.findProgramAddress(
+    [Buffer.from(VAULT_WEIGHT_SEED), vault.toBuffer(), mint.toBuffer()],
+    program.programId
+  );
+}
+
+async function getEscrowAddress(
+  vault: PublicKey,
+  program: Program<XTokenStake>
+): Promise<[PublicKey, number]> {
+  return await PublicKey.findProgramAddress(
+    [Buffer.from(VAULT_ESCROW_SEED), vault.toBuffer()],
+    program.programId
+  );
+}
+
 async function spawnMoney(
   program: anchor.Program<XTokenStake>,
   to: PublicKey,
@@ -57,7 +156,11 @@ async function spawnMoney(
   });
 }
 
-async function createVault(program: Program<XTokenStake>): Promise<{
+async function createVault(
+  program: Program<XTokenStake>,
+  stakeMint?: Mint,
+  custodyMode?: CustodyMode
+): Promise<{
   mint: Mint;
   authority: Keypair;
   vault: Vault;
@@ -65,12 +168,14 @@ async function createVault(program: Program<XTokenStake>): Promise<{
   // create reward token
   const mint = await Mint.create(program);
 
-  // create vault
+  // create vault, a fungible one when a stake mint is given
   const { authority, vault } = await Vault.create({
     program,
     mint,
     duration: 1,
     stakeTokenCount: 500000,
+    stakeMint,
+    custodyMode,
   });
 
   return {
@@ -124,9 +229,16 @@ async function getTokenAmounts(
 }
 
 export {
+  getContributionAddress,
+  getRoleAddress,
+  getFreezeAddress,
   toPublicKey,
   getRewardAddress,
   getUserAddress,
+  getWhitelistAddress,
+  getReceiptAddress,
+  getEscrowAddress,
+  getWeightAddress,
   spawnMoney,
   createVault,
   sleep,
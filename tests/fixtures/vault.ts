@@ -12,11 +12,33 @@ import {
   SystemProgram,
 } from "@solana/web3.js";
 import { Mint } from "./mint";
-import { getRewardAddress, getUserAddress, spawnMoney } from "./lib";
+import {
+  getContributionAddress,
+  getEscrowAddress,
+  getFreezeAddress,
+  getReceiptAddress,
+  getRoleAddress,
+  getRewardAddress,
+  getUserAddress,
+  getWeightAddress,
+  getWhitelistAddress,
+  spawnMoney,
+} from "./lib";
 import { TokenAccount } from "./token-account";
 
 const VAULT_STAKE_SEED = "x_token_vault_stake";
+
+export type RewardStreamAccounts = {
+  mint: Mint;
+  mintAccount: PublicKey;
+};
+
 export class Vault {
+  // reward streams in the order of the vault, the first one is created with the vault
+  public rewardStreams: RewardStreamAccounts[];
+  // how staked nfts are held, set at creation
+  public custodyMode: CustodyMode = { setAuthority: {} };
+
   constructor(
     public program: anchor.Program<XTokenStake>,
     public key: PublicKey,
@@ -24,7 +46,9 @@ export class Vault {
     public mintAccount: PublicKey,
     public mintCount: number,
     public rewardDuration: number
-  ) {}
+  ) {
+    this.rewardStreams = [{ mint, mintAccount }];
+  }
 
   async fetch(): Promise<VaultData | null> {
     return (await this.program.account.vault.fetchNullable(
@@ -38,6 +62,19 @@ export class Vault {
     )) as UserData | null;
   }
 
+  async fetchReceipt(
+    stakeAccount: PublicKey
+  ): Promise<StakeReceiptData | null> {
+    const [receipt, _] = await getReceiptAddress(
+      this.key,
+      stakeAccount,
+      this.program
+    );
+    return (await this.program.account.stakeReceipt.fetchNullable(
+      receipt
+    )) as StakeReceiptData | null;
+  }
+
   static async create({
     authority = Keypair.generate(),
     vaultKey = Keypair.generate(),
@@ -45,6 +82,8 @@ export class Vault {
     mint,
     duration,
     stakeTokenCount,
+    stakeMint,
+    custodyMode = { setAuthority: {} },
   }: {
     authority?: Keypair;
     vaultKey?: Keypair;
@@ -52,6 +91,8 @@ export class Vault {
     mint: Mint;
     duration: number;
     stakeTokenCount: number;
+    stakeMint?: Mint;
+    custodyMode?: CustodyMode;
   }): Promise<{
     authority: Keypair;
     vault: Vault;
@@ -70,6 +111,9 @@ export class Vault {
       rewardBump,
       new anchor.BN(duration),
       stakeTokenCount,
+      stakeMint ? { fungible: {} } : { nft: {} },
+      stakeMint ? stakeMint.key : PublicKey.default,
+      custodyMode,
       {
         accounts: {
           authority: authority.publicKey,
@@ -88,33 +132,91 @@ export class Vault {
         },
       }
     );
+    const vault = new Vault(
+      program,
+      vaultKey.publicKey,
+      mint,
+      mintAccount,
+      stakeTokenCount,
+      duration
+    );
+    vault.custodyMode = custodyMode;
     return {
       authority,
-      vault: new Vault(
-        program,
-        vaultKey.publicKey,
-        mint,
-        mintAccount,
-        stakeTokenCount,
-        duration
-      ),
+      vault,
       sig: txSignature,
     };
   }
 
-  async addFunder(
+  // escrow token account of the vault pda of a staker, it holds the staked token in escrow custody
+  async getStakeEscrow(
+    staker: PublicKey,
+    stakeMint: Mint
+  ): Promise<{
+    vaultPda: PublicKey;
+    escrowAccount: PublicKey;
+  }> {
+    const [vaultPda, _] = await PublicKey.findProgramAddress(
+      [Buffer.from(VAULT_STAKE_SEED), this.key.toBuffer(), staker.toBuffer()],
+      this.program.programId
+    );
+    const escrowAccount = await stakeMint.getAssociatedTokenAddress(vaultPda);
+    return { vaultPda, escrowAccount };
+  }
+
+  // a stake mint of a freeze custody vault hands its freeze authority to the vault freeze pda
+  async createStakeMint(): Promise<Mint> {
+    if (!this.custodyMode.freeze) {
+      return await Mint.create(this.program);
+    }
+    const [freezeAuthority, _] = await getFreezeAddress(this.key, this.program);
+    return await Mint.create(
+      this.program,
+      undefined,
+      undefined,
+      freezeAuthority
+    );
+  }
+
+  // the escrow token account is created by the staker before staking in escrow custody
+  async createStakeEscrow(
+    staker: PublicKey,
+    stakeMint: Mint
+  ): Promise<PublicKey> {
+    const { vaultPda, escrowAccount } = await this.getStakeEscrow(
+      staker,
+      stakeMint
+    );
+    if (this.custodyMode.escrow) {
+      await stakeMint.createAssociatedAccount(vaultPda);
+    }
+    return escrowAccount;
+  }
+
+  async addRewardStream(
     authority: Keypair,
-    funder = Keypair.generate()
+    mint: Mint,
+    duration: number
   ): Promise<{
-    funderAdded: Keypair;
+    mintAccount: PublicKey;
     sig: TransactionSignature;
   }> {
-    const txSignature = await this.program.rpc.authorizeFunder(
-      funder.publicKey,
+    const [reward, _] = await getRewardAddress(this.key, this.program);
+    const mintAccount = await mint.getAssociatedTokenAddress(reward);
+
+    const txSignature = await this.program.rpc.addRewardStream(
+      new anchor.BN(duration),
       {
         accounts: {
           authority: authority.publicKey,
           vault: this.key,
+          reward,
+          rewardMint: mint.key,
+          rewardAccount: mintAccount,
+          rent: SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedToken: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
         },
         signers: [authority],
         options: {
@@ -122,56 +224,85 @@ export class Vault {
         },
       }
     );
+    this.rewardStreams.push({ mint, mintAccount });
     return {
-      funderAdded: funder,
+      mintAccount,
       sig: txSignature,
     };
   }
 
-  async removeFunder(
+  // role entry proving the role of a holder, passed as the first remaining account,
+  // a funder role is scoped to the reward mint of one stream
+  async roleAccounts(
+    holder: PublicKey,
+    role: Role,
+    rewardMint = PublicKey.default
+  ): Promise<anchor.web3.AccountMeta[]> {
+    const [vaultRole, _] = await getRoleAddress(
+      this.key,
+      holder,
+      role,
+      rewardMint,
+      this.program
+    );
+    return [{ pubkey: vaultRole, isWritable: false, isSigner: false }];
+  }
+
+  async grantRole(
     authority: Keypair,
-    funder: PublicKey
+    role: Role,
+    holder = Keypair.generate(),
+    rewardMint = PublicKey.default
   ): Promise<{
+    holder: Keypair;
+    vaultRole: PublicKey;
     sig: TransactionSignature;
   }> {
-    const txSignature = await this.program.rpc.unauthorizeFunder(funder, {
-      accounts: {
-        authority: authority.publicKey,
-        vault: this.key,
-      },
-      signers: [authority],
-      options: {
-        commitment: "confirmed",
-      },
-    });
+    const [vaultRole, roleBump] = await getRoleAddress(
+      this.key,
+      holder.publicKey,
+      role,
+      rewardMint,
+      this.program
+    );
+    const txSignature = await this.program.rpc.grantRole(
+      roleBump,
+      holder.publicKey,
+      role,
+      rewardMint,
+      {
+        accounts: {
+          authority: authority.publicKey,
+          vault: this.key,
+          vaultRole,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [authority],
+        options: {
+          commitment: "confirmed",
+        },
+      }
+    );
     return {
+      holder,
+      vaultRole,
       sig: txSignature,
     };
   }
 
-  async fund({
-    authority,
-    funder,
-    funderAccount,
-    amount,
-  }: {
-    authority: Keypair;
-    funder: Keypair;
-    funderAccount: PublicKey;
-    amount: anchor.BN;
-  }): Promise<{
+  async revokeRole(
+    authority: Keypair,
+    vaultRole: PublicKey
+  ): Promise<{
     sig: TransactionSignature;
   }> {
-    const txSignature = await this.program.rpc.fund(amount, {
+    const txSignature = await this.program.rpc.revokeRole({
       accounts: {
-        funder: funder.publicKey,
         authority: authority.publicKey,
         vault: this.key,
-        rewardAccount: this.mintAccount,
-        funderAccount,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        vaultRole,
       },
-      signers: [funder],
+      signers: [authority],
       options: {
         commitment: "confirmed",
       },
@@ -181,208 +312,1151 @@ export class Vault {
     };
   }
 
-  async createUser(authority = Keypair.generate()): Promise<{
-    authority: Keypair;
-    user: PublicKey;
+  async addFunder(
+    authority: Keypair,
+    funder = Keypair.generate(),
+    rewardMint = this.mint.key
+  ): Promise<{
+    funderAdded: Keypair;
     sig: TransactionSignature;
   }> {
-    await spawnMoney(this.program, authority.publicKey, 10);
-    const [userAddress, userBump] = await getUserAddress(
-      this.key,
-      authority.publicKey,
-      this.program
+    const { sig } = await this.grantRole(
+      authority,
+      { funder: {} },
+      funder,
+      rewardMint
     );
+    // funders pay for their contribution entries
+    await spawnMoney(this.program, funder.publicKey, 1);
+    return {
+      funderAdded: funder,
+      sig,
+    };
+  }
 
-    const txSignature = await this.program.rpc.createUser(userBump, {
+  async pause(
+    authority: Keypair,
+    remainingAccounts: anchor.web3.AccountMeta[] = []
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.pauseVault({
       accounts: {
         authority: authority.publicKey,
         vault: this.key,
-        user: userAddress,
-        systemProgram: SystemProgram.programId,
       },
+      remainingAccounts,
       signers: [authority],
       options: {
         commitment: "confirmed",
       },
     });
-
     return {
-      authority,
-      user: userAddress,
       sig: txSignature,
     };
   }
 
-  async closeUser(authority: Keypair, user: PublicKey): Promise<boolean> {
-    await this.program.rpc.closeUser({
+  async resume(authority: Keypair): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.resumeVault({
       accounts: {
         authority: authority.publicKey,
         vault: this.key,
-        user,
       },
       signers: [authority],
       options: {
         commitment: "confirmed",
       },
     });
-    return true;
+    return {
+      sig: txSignature,
+    };
   }
 
-  async stake(
-    curAuthoriy?: Keypair,
-    curUser?: PublicKey
+  async proposeAuthority(
+    authority: Keypair,
+    pendingAuthority: PublicKey
   ): Promise<{
-    userAuthority: Keypair;
-    user: PublicKey;
-    stakeAccount: TokenAccount<PublicKey>;
-    stakeMint: Mint;
+    sig: TransactionSignature;
   }> {
-    let userAuthority: Keypair;
-    let user: PublicKey;
-
-    if (!curUser) {
-      // create user
-      const { authority, user: created } = await this.createUser();
-      userAuthority = authority;
-      user = created;
-    } else {
-      userAuthority = curAuthoriy;
-      user = curUser;
-    }
-
-    // create a token to be staked and its account of userAuthority
-    const stakeMint = await Mint.create(this.program);
-    const stakeAccount = await stakeMint.createAssociatedAccount(
-      userAuthority.publicKey
+    const txSignature = await this.program.rpc.proposeAuthority(
+      pendingAuthority,
+      {
+        accounts: {
+          authority: authority.publicKey,
+          vault: this.key,
+        },
+        signers: [authority],
+        options: {
+          commitment: "confirmed",
+        },
+      }
     );
-    await stakeMint.mintTokens(stakeAccount, 1);
+    return {
+      sig: txSignature,
+    };
+  }
 
-    // stake
-    await this.program.rpc.stake({
+  async acceptAuthority(pendingAuthority: Keypair): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.acceptAuthority({
       accounts: {
-        staker: userAuthority.publicKey,
+        pendingAuthority: pendingAuthority.publicKey,
         vault: this.key,
-        stakeAccount: stakeAccount.key,
-        user,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
       },
-      signers: [userAuthority],
-      options: { commitment: "confirmed" },
+      signers: [pendingAuthority],
+      options: {
+        commitment: "confirmed",
+      },
     });
-
-    return { userAuthority, user, stakeAccount, stakeMint };
+    return {
+      sig: txSignature,
+    };
   }
 
-  async unstake(
+  async updateConfig(
     authority: Keypair,
-    user: PublicKey,
-    stakeAccount: TokenAccount<PublicKey>
-  ): Promise<boolean> {
-    const [vaultPda, vaultStakeBump] = await PublicKey.findProgramAddress(
-      [
-        Buffer.from(VAULT_STAKE_SEED),
-        this.key.toBuffer(),
-        authority.publicKey.toBuffer(),
-      ],
-      this.program.programId
-    );
-
-    await this.program.rpc.unstake(vaultStakeBump, {
+    config: VaultConfig
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.updateVaultConfig(config, {
       accounts: {
-        staker: authority.publicKey,
+        authority: authority.publicKey,
         vault: this.key,
-        unstakeAccount: stakeAccount.key,
-        vaultPda,
-        user,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
       },
       signers: [authority],
-      options: { commitment: "confirmed" },
-    });
-    return true;
-  }
-
-  async claim(vaultAuthority: PublicKey, claimer: Keypair, user: PublicKey) {
-    const claimerAccount = await this.mint.getAssociatedTokenAddress(
-      claimer.publicKey
-    );
-    const [reward, _] = await getRewardAddress(this.key, this.program);
-
-    await this.program.rpc.claim({
-      accounts: {
-        claimer: claimer.publicKey,
-        vault: this.key,
-        authority: vaultAuthority,
-        reward,
-        rewardMint: this.mint.key,
-        rewardMintAccount: this.mintAccount,
-        rewardAccount: claimerAccount,
-        user,
-        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-        rent: SYSVAR_RENT_PUBKEY,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
+      options: {
+        commitment: "confirmed",
       },
-      signers: [claimer],
-      options: { commitment: "confirmed" },
     });
+    return {
+      sig: txSignature,
+    };
   }
 
-  async close(
+  async setCollection(
     authority: Keypair,
-    refundee: Keypair,
-    refundAccount: PublicKey
-  ): Promise<boolean> {
-    const [reward, _] = await getRewardAddress(this.key, this.program);
-    this.program.rpc.closeVault({
+    collection: PublicKey
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.setCollection(collection, {
       accounts: {
         authority: authority.publicKey,
         vault: this.key,
-        reward,
-        rewardMint: this.mint.key,
-        refundee: refundee.publicKey,
-        refundAccount,
-        rewardMintAccount: this.mintAccount,
-        rent: SYSVAR_RENT_PUBKEY,
-        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
       },
       signers: [authority],
+      options: {
+        commitment: "confirmed",
+      },
     });
-    return true;
+    return {
+      sig: txSignature,
+    };
   }
-}
 
-export type VaultStatus = {
-  none?: {};
-  initialized?: {};
-  paused?: {};
-};
+  async setEmissionSchedule(
+    authority: Keypair,
+    schedule: EmissionSchedule,
+    rewardIndex = 0
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.setEmissionSchedule(
+      rewardIndex,
+      schedule,
+      {
+        accounts: {
+          authority: authority.publicKey,
+          vault: this.key,
+        },
+        signers: [authority],
+        options: {
+          commitment: "confirmed",
+        },
+      }
+    );
+    return {
+      sig: txSignature,
+    };
+  }
+
+  async setLockTiers(
+    authority: Keypair,
+    lockTiers: LockTier[],
+    earlyUnstakePenalty: number
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.setLockTiers(
+      lockTiers,
+      earlyUnstakePenalty,
+      {
+        accounts: {
+          authority: authority.publicKey,
+          vault: this.key,
+        },
+        signers: [authority],
+        options: {
+          commitment: "confirmed",
+        },
+      }
+    );
+    return {
+      sig: txSignature,
+    };
+  }
+
+  async setMintWeight(
+    authority: Keypair,
+    mint: PublicKey,
+    weight: number
+  ): Promise<{
+    mintWeight: PublicKey;
+    sig: TransactionSignature;
+  }> {
+    const [mintWeight, weightBump] = await getWeightAddress(
+      this.key,
+      mint,
+      this.program
+    );
+
+    const txSignature = await this.program.rpc.setMintWeight(
+      weightBump,
+      mint,
+      weight,
+      {
+        accounts: {
+          authority: authority.publicKey,
+          vault: this.key,
+          mintWeight,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [authority],
+        options: {
+          commitment: "confirmed",
+        },
+      }
+    );
+    return {
+      mintWeight,
+      sig: txSignature,
+    };
+  }
+
+  async removeMintWeight(
+    authority: Keypair,
+    mintWeight: PublicKey
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.removeMintWeight({
+      accounts: {
+        authority: authority.publicKey,
+        vault: this.key,
+        mintWeight,
+      },
+      signers: [authority],
+      options: {
+        commitment: "confirmed",
+      },
+    });
+    return {
+      sig: txSignature,
+    };
+  }
+
+  async addWhitelist(
+    authority: Keypair,
+    address: PublicKey,
+    whitelistType: WhitelistType
+  ): Promise<{
+    whitelist: PublicKey;
+    sig: TransactionSignature;
+  }> {
+    const [whitelist, whitelistBump] = await getWhitelistAddress(
+      this.key,
+      address,
+      this.program
+    );
+
+    const txSignature = await this.program.rpc.addWhitelist(
+      whitelistBump,
+      address,
+      whitelistType,
+      {
+        accounts: {
+          authority: authority.publicKey,
+          vault: this.key,
+          whitelist,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [authority],
+        options: {
+          commitment: "confirmed",
+        },
+      }
+    );
+    return {
+      whitelist,
+      sig: txSignature,
+    };
+  }
+
+  async removeWhitelist(
+    authority: Keypair,
+    whitelist: PublicKey
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.removeWhitelist({
+      accounts: {
+        authority: authority.publicKey,
+        vault: this.key,
+        whitelist,
+      },
+      signers: [authority],
+      options: {
+        commitment: "confirmed",
+      },
+    });
+    return {
+      sig: txSignature,
+    };
+  }
+
+  async fund({
+    funder,
+    funderAccount,
+    amount,
+    rewardAccount = this.mintAccount,
+    role = { funder: {} },
+  }: {
+    funder: Keypair;
+    funderAccount: PublicKey;
+    amount: anchor.BN;
+    rewardAccount?: PublicKey;
+    role?: Role;
+  }): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const { mint } = this.rewardStreams.find((stream) =>
+      stream.mintAccount.equals(rewardAccount)
+    );
+    const [contribution, contributionBump] = await getContributionAddress(
+      this.key,
+      mint.key,
+      funder.publicKey,
+      this.program
+    );
+
+    // the role entry is ignored when the funder is the vault authority
+    const txSignature = await this.program.rpc.fund(amount, contributionBump, {
+      accounts: {
+        funder: funder.publicKey,
+        vault: this.key,
+        rewardAccount,
+        funderAccount,
+        contribution,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts: await this.roleAccounts(
+        funder.publicKey,
+        role,
+        "funder" in role ? mint.key : PublicKey.default
+      ),
+      signers: [funder],
+      options: {
+        commitment: "confirmed",
+      },
+    });
+    return {
+      sig: txSignature,
+    };
+  }
+
+  // anyone can settle a contribution, the caller defaults to the funder
+  async withdrawUnallocated({
+    funder,
+    funderAccount,
+    rewardIndex = 0,
+    caller = funder,
+  }: {
+    funder: Keypair;
+    funderAccount: PublicKey;
+    rewardIndex?: number;
+    caller?: Keypair;
+  }): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const { mint, mintAccount } = this.rewardStreams[rewardIndex];
+    const [reward, _] = await getRewardAddress(this.key, this.program);
+    const [contribution, __] = await getContributionAddress(
+      this.key,
+      mint.key,
+      funder.publicKey,
+      this.program
+    );
+
+    const txSignature = await this.program.rpc.withdrawUnallocated({
+      accounts: {
+        caller: caller.publicKey,
+        funder: funder.publicKey,
+        vault: this.key,
+        reward,
+        rewardAccount: mintAccount,
+        funderAccount,
+        contribution,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      signers: [caller],
+      options: {
+        commitment: "confirmed",
+      },
+    });
+    return {
+      sig: txSignature,
+    };
+  }
+
+  async createUser(authority = Keypair.generate()): Promise<{
+    authority: Keypair;
+    user: PublicKey;
+    sig: TransactionSignature;
+  }> {
+    await spawnMoney(this.program, authority.publicKey, 10);
+    const [userAddress, userBump] = await getUserAddress(
+      this.key,
+      authority.publicKey,
+      this.program
+    );
+
+    const txSignature = await this.program.rpc.createUser(userBump, {
+      accounts: {
+        authority: authority.publicKey,
+        vault: this.key,
+        user: userAddress,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [authority],
+      options: {
+        commitment: "confirmed",
+      },
+    });
+
+    return {
+      authority,
+      user: userAddress,
+      sig: txSignature,
+    };
+  }
+
+  async closeUser(authority: Keypair, user: PublicKey): Promise<boolean> {
+    await this.program.rpc.closeUser({
+      accounts: {
+        authority: authority.publicKey,
+        vault: this.key,
+        user,
+      },
+      signers: [authority],
+      options: {
+        commitment: "confirmed",
+      },
+    });
+    return true;
+  }
+
+  async stake(
+    curAuthoriy?: Keypair,
+    curUser?: PublicKey,
+    curStakeMint?: Mint,
+    remainingAccounts: anchor.web3.AccountMeta[] = [],
+    lockTier: number | null = null
+  ): Promise<{
+    userAuthority: Keypair;
+    user: PublicKey;
+    stakeAccount: TokenAccount<PublicKey>;
+    stakeMint: Mint;
+  }> {
+    let userAuthority: Keypair;
+    let user: PublicKey;
+
+    if (!curUser) {
+      // create user
+      const { authority, user: created } = await this.createUser();
+      userAuthority = authority;
+      user = created;
+    } else {
+      userAuthority = curAuthoriy;
+      user = curUser;
+    }
+
+    // create a token to be staked and its account of userAuthority
+    const stakeMint = curStakeMint ?? (await this.createStakeMint());
+    const stakeAccount = await stakeMint.createAssociatedAccount(
+      userAuthority.publicKey
+    );
+    await stakeMint.mintTokens(stakeAccount, 1);
+
+    const [receipt, receiptBump] = await getReceiptAddress(
+      this.key,
+      stakeAccount.key,
+      this.program
+    );
+    const { vaultPda } = await this.getStakeEscrow(
+      userAuthority.publicKey,
+      stakeMint
+    );
+    const escrowAccount = await this.createStakeEscrow(
+      userAuthority.publicKey,
+      stakeMint
+    );
+    const [freezeAuthority, _] = await getFreezeAddress(this.key, this.program);
+
+    // stake
+    await this.program.rpc.stake(receiptBump, lockTier, {
+      accounts: {
+        staker: userAuthority.publicKey,
+        vault: this.key,
+        stakeAccount: stakeAccount.key,
+        receipt,
+        user,
+        vaultPda,
+        escrowAccount,
+        stakeMint: stakeMint.key,
+        freezeAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [userAuthority],
+      options: { commitment: "confirmed" },
+    });
+
+    return { userAuthority, user, stakeAccount, stakeMint };
+  }
+
+  async stakeMany(
+    userAuthority: Keypair,
+    user: PublicKey,
+    count: number,
+    lockTier: number | null = null
+  ): Promise<{
+    stakeAccounts: TokenAccount<PublicKey>[];
+  }> {
+    // create tokens to be staked and their accounts of userAuthority
+    const stakeAccounts: TokenAccount<PublicKey>[] = [];
+    for (let i = 0; i < count; i++) {
+      const stakeMint = await this.createStakeMint();
+      const stakeAccount = await stakeMint.createAssociatedAccount(
+        userAuthority.publicKey
+      );
+      await stakeMint.mintTokens(stakeAccount, 1);
+      stakeAccounts.push(stakeAccount);
+    }
+
+    // each stake account is followed by its stake receipt and by its escrow in escrow custody
+    const remainingAccounts: anchor.web3.AccountMeta[] = [];
+    for (const stakeAccount of stakeAccounts) {
+      const [receipt, _] = await getReceiptAddress(
+        this.key,
+        stakeAccount.key,
+        this.program
+      );
+      remainingAccounts.push(
+        { pubkey: stakeAccount.key, isWritable: true, isSigner: false },
+        { pubkey: receipt, isWritable: true, isSigner: false }
+      );
+      if (this.custodyMode.escrow) {
+        const escrowAccount = await this.createStakeEscrow(
+          userAuthority.publicKey,
+          stakeAccount.mint
+        );
+        remainingAccounts.push({
+          pubkey: escrowAccount,
+          isWritable: true,
+          isSigner: false,
+        });
+      }
+      if (this.custodyMode.freeze) {
+        remainingAccounts.push({
+          pubkey: stakeAccount.mint.key,
+          isWritable: false,
+          isSigner: false,
+        });
+      }
+    }
+    const [vaultPda, _] = await PublicKey.findProgramAddress(
+      [
+        Buffer.from(VAULT_STAKE_SEED),
+        this.key.toBuffer(),
+        userAuthority.publicKey.toBuffer(),
+      ],
+      this.program.programId
+    );
+    const [freezeAuthority, __] = await getFreezeAddress(
+      this.key,
+      this.program
+    );
+
+    await this.program.rpc.stakeMany(lockTier, {
+      accounts: {
+        staker: userAuthority.publicKey,
+        vault: this.key,
+        user,
+        vaultPda,
+        freezeAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [userAuthority],
+      options: { commitment: "confirmed" },
+    });
+
+    return { stakeAccounts };
+  }
+
+  async unstakeMany(
+    authority: Keypair,
+    user: PublicKey,
+    stakeAccounts: TokenAccount<PublicKey>[]
+  ): Promise<boolean> {
+    const [vaultPda, vaultStakeBump] = await PublicKey.findProgramAddress(
+      [
+        Buffer.from(VAULT_STAKE_SEED),
+        this.key.toBuffer(),
+        authority.publicKey.toBuffer(),
+      ],
+      this.program.programId
+    );
+
+    // each stake account is followed by its stake receipt and by its escrow in escrow custody
+    const remainingAccounts: anchor.web3.AccountMeta[] = [];
+    for (const stakeAccount of stakeAccounts) {
+      const [receipt, _] = await getReceiptAddress(
+        this.key,
+        stakeAccount.key,
+        this.program
+      );
+      remainingAccounts.push(
+        { pubkey: stakeAccount.key, isWritable: true, isSigner: false },
+        { pubkey: receipt, isWritable: true, isSigner: false }
+      );
+      if (this.custodyMode.escrow) {
+        const { escrowAccount } = await this.getStakeEscrow(
+          authority.publicKey,
+          stakeAccount.mint
+        );
+        remainingAccounts.push({
+          pubkey: escrowAccount,
+          isWritable: true,
+          isSigner: false,
+        });
+      }
+      if (this.custodyMode.freeze) {
+        remainingAccounts.push({
+          pubkey: stakeAccount.mint.key,
+          isWritable: false,
+          isSigner: false,
+        });
+      }
+    }
+    const [freezeAuthority, _] = await getFreezeAddress(
+      this.key,
+      this.program
+    );
+
+    await this.program.rpc.unstakeMany(vaultStakeBump, {
+      accounts: {
+        staker: authority.publicKey,
+        vault: this.key,
+        vaultPda,
+        user,
+        freezeAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return true;
+  }
+
+  async unstake(
+    authority: Keypair,
+    user: PublicKey,
+    stakeAccount: TokenAccount<PublicKey>
+  ): Promise<boolean> {
+    const [vaultPda, vaultStakeBump] = await PublicKey.findProgramAddress(
+      [
+        Buffer.from(VAULT_STAKE_SEED),
+        this.key.toBuffer(),
+        authority.publicKey.toBuffer(),
+      ],
+      this.program.programId
+    );
+
+    const [receipt, _] = await getReceiptAddress(
+      this.key,
+      stakeAccount.key,
+      this.program
+    );
+    const { escrowAccount } = await this.getStakeEscrow(
+      authority.publicKey,
+      stakeAccount.mint
+    );
+    const [freezeAuthority, __] = await getFreezeAddress(
+      this.key,
+      this.program
+    );
 
-export type VaultData = {
-  authority: PublicKey;
-  status: VaultStatus;
+    await this.program.rpc.unstake(vaultStakeBump, {
+      accounts: {
+        staker: authority.publicKey,
+        vault: this.key,
+        unstakeAccount: stakeAccount.key,
+        receipt,
+        vaultPda,
+        user,
+        escrowAccount,
+        stakeMint: stakeAccount.mint.key,
+        freezeAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return true;
+  }
+
+  async deposit(
+    authority: Keypair,
+    user: PublicKey,
+    stakeMint: Mint,
+    stakerAccount: PublicKey,
+    amount: anchor.BN
+  ): Promise<{
+    escrowAccount: PublicKey;
+  }> {
+    const [escrow, escrowBump] = await getEscrowAddress(
+      this.key,
+      this.program
+    );
+    const escrowAccount = await stakeMint.getAssociatedTokenAddress(escrow);
+
+    await this.program.rpc.deposit(escrowBump, amount, {
+      accounts: {
+        staker: authority.publicKey,
+        vault: this.key,
+        escrow,
+        stakeMint: stakeMint.key,
+        escrowAccount,
+        stakerAccount,
+        user,
+        rent: SYSVAR_RENT_PUBKEY,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return { escrowAccount };
+  }
+
+  async withdraw(
+    authority: Keypair,
+    user: PublicKey,
+    stakeMint: Mint,
+    stakerAccount: PublicKey,
+    amount: anchor.BN
+  ): Promise<boolean> {
+    const [escrow, escrowBump] = await getEscrowAddress(
+      this.key,
+      this.program
+    );
+    const escrowAccount = await stakeMint.getAssociatedTokenAddress(escrow);
+
+    await this.program.rpc.withdraw(escrowBump, amount, {
+      accounts: {
+        staker: authority.publicKey,
+        vault: this.key,
+        escrow,
+        escrowAccount,
+        stakerAccount,
+        user,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return true;
+  }
+
+  async compound(
+    authority: Keypair,
+    user: PublicKey,
+    stakeMint: Mint,
+    rewardAccount = this.mintAccount
+  ): Promise<boolean> {
+    const [escrow, escrowBump] = await getEscrowAddress(
+      this.key,
+      this.program
+    );
+    const [reward, _rewardBump] = await getRewardAddress(
+      this.key,
+      this.program
+    );
+    const escrowAccount = await stakeMint.getAssociatedTokenAddress(escrow);
+
+    await this.program.rpc.compound(escrowBump, {
+      accounts: {
+        staker: authority.publicKey,
+        vault: this.key,
+        reward,
+        rewardAccount,
+        escrow,
+        escrowAccount,
+        user,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return true;
+  }
+
+  async emergencyUnstake(
+    authority: Keypair,
+    user: PublicKey,
+    stakeAccount: TokenAccount<PublicKey>
+  ): Promise<boolean> {
+    const [vaultPda, vaultStakeBump] = await PublicKey.findProgramAddress(
+      [
+        Buffer.from(VAULT_STAKE_SEED),
+        this.key.toBuffer(),
+        authority.publicKey.toBuffer(),
+      ],
+      this.program.programId
+    );
+
+    const [receipt, _] = await getReceiptAddress(
+      this.key,
+      stakeAccount.key,
+      this.program
+    );
+    const { escrowAccount } = await this.getStakeEscrow(
+      authority.publicKey,
+      stakeAccount.mint
+    );
+    const [freezeAuthority, __] = await getFreezeAddress(
+      this.key,
+      this.program
+    );
+
+    await this.program.rpc.emergencyUnstake(vaultStakeBump, {
+      accounts: {
+        staker: authority.publicKey,
+        vault: this.key,
+        unstakeAccount: stakeAccount.key,
+        receipt,
+        vaultPda,
+        user,
+        escrowAccount,
+        stakeMint: stakeAccount.mint.key,
+        freezeAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return true;
+  }
+
+  // every reward stream contributes its mint, vault reward account and recipient account
+  async rewardStreamAccounts(
+    recipient: PublicKey,
+    rewardStreams: RewardStreamAccounts[]
+  ): Promise<anchor.web3.AccountMeta[]> {
+    const remainingAccounts: anchor.web3.AccountMeta[] = [];
+    for (const { mint, mintAccount } of rewardStreams) {
+      const recipientAccount = await mint.getAssociatedTokenAddress(recipient);
+      remainingAccounts.push(
+        { pubkey: mint.key, isWritable: false, isSigner: false },
+        { pubkey: mintAccount, isWritable: true, isSigner: false },
+        { pubkey: recipientAccount, isWritable: true, isSigner: false }
+      );
+    }
+    return remainingAccounts;
+  }
+
+  async claim(
+    vaultAuthority: PublicKey,
+    claimer: Keypair,
+    user: PublicKey,
+    rewardIndex: number | null = null
+  ) {
+    const [reward, _] = await getRewardAddress(this.key, this.program);
+    const remainingAccounts = await this.rewardStreamAccounts(
+      claimer.publicKey,
+      rewardIndex === null
+        ? this.rewardStreams
+        : [this.rewardStreams[rewardIndex]]
+    );
+
+    await this.program.rpc.claim(rewardIndex, {
+      accounts: {
+        claimer: claimer.publicKey,
+        vault: this.key,
+        authority: vaultAuthority,
+        reward,
+        user,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [claimer],
+      options: { commitment: "confirmed" },
+    });
+  }
+
+  async setRewardRecipient(
+    authority: Keypair,
+    user: PublicKey,
+    recipient: PublicKey
+  ): Promise<{
+    sig: TransactionSignature;
+  }> {
+    const txSignature = await this.program.rpc.setRewardRecipient(recipient, {
+      accounts: {
+        authority: authority.publicKey,
+        user,
+      },
+      signers: [authority],
+      options: { commitment: "confirmed" },
+    });
+    return {
+      sig: txSignature,
+    };
+  }
+
+  async crankClaim(
+    caller: Keypair,
+    user: PublicKey,
+    recipient: PublicKey,
+    rewardIndex: number | null = null
+  ) {
+    const [reward, _] = await getRewardAddress(this.key, this.program);
+    const remainingAccounts = await this.rewardStreamAccounts(
+      recipient,
+      rewardIndex === null
+        ? this.rewardStreams
+        : [this.rewardStreams[rewardIndex]]
+    );
+
+    await this.program.rpc.crankClaim(rewardIndex, {
+      accounts: {
+        caller: caller.publicKey,
+        vault: this.key,
+        reward,
+        user,
+        recipient,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [caller],
+      options: { commitment: "confirmed" },
+    });
+  }
+
+  async close(authority: Keypair, refundee: Keypair): Promise<boolean> {
+    const [reward, _] = await getRewardAddress(this.key, this.program);
+    const remainingAccounts = await this.rewardStreamAccounts(
+      refundee.publicKey,
+      this.rewardStreams
+    );
+    await this.program.rpc.closeVault({
+      accounts: {
+        authority: authority.publicKey,
+        vault: this.key,
+        reward,
+        refundee: refundee.publicKey,
+        rent: SYSVAR_RENT_PUBKEY,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      },
+      remainingAccounts,
+      signers: [authority],
+    });
+    return true;
+  }
+}
+
+export type VaultStatus = {
+  none?: {};
+  initialized?: {};
+  paused?: {};
+};
+
+export type StakeMode = {
+  nft?: {};
+  fungible?: {};
+};
+
+export type Role = {
+  admin?: {};
+  funder?: {};
+  pauser?: {};
+  whitelistManager?: {};
+};
+
+export type VaultRoleData = {
+  vault: PublicKey;
+  holder: PublicKey;
+  role: Role;
+  rewardMint: PublicKey;
+};
+
+export type CustodyMode = {
+  setAuthority?: {};
+  escrow?: {};
+  freeze?: {};
+};
+
+export type LockTier = {
+  duration: anchor.BN;
+  multiplier: number;
+};
+
+export type WhitelistType = {
+  mint?: {};
+  creator?: {};
+};
+
+export type EmissionCurve = {
+  constant?: {};
+  linearDecay?: {};
+  stepHalving?: {};
+  piecewise?: {};
+};
+
+export type EmissionCheckpoint = {
+  offset: anchor.BN;
+  rate: number;
+};
+
+export type EmissionSchedule = {
+  curve: EmissionCurve;
+  param: anchor.BN;
+  checkpointCount: number;
+  checkpoints: EmissionCheckpoint[];
+};
+
+export type RewardStreamData = {
   rewardMint: PublicKey;
-  rewardBump: number;
   rewardMintAccount: PublicKey;
   rewardDuration: anchor.BN;
   rewardDurationDeadline: anchor.BN;
   rewardRate: anchor.BN;
+  rewardPerTokenStored: anchor.BN;
+  lastUpdateTime: anchor.BN;
+  rewardLiability: anchor.BN;
+  totalContributed: anchor.BN;
+  contributionCount: number;
+  refunding: boolean;
+  periodStart: anchor.BN;
+  schedule: EmissionSchedule;
+};
+
+export type VaultData = {
+  authority: PublicKey;
+  status: VaultStatus;
+  rewardBump: number;
+  stakeMode: StakeMode;
+  stakeMint: PublicKey;
+  custodyMode: CustodyMode;
+  rewardStreamCount: number;
+  rewardStreams: RewardStreamData[];
+  pausedAt: anchor.BN;
   stakedCount: number;
+  stakedWeight: anchor.BN;
   stakeTokenCount: number;
+  maxStakePerUser: number;
   userCount: number;
-  funders: PublicKey[];
+  whitelistedMints: number;
+  whitelistedCreators: number;
+  lockTierCount: number;
+  lockTiers: LockTier[];
+  earlyUnstakePenalty: number;
+  weightedMints: number;
+  collection: PublicKey;
+  pendingAuthority: PublicKey;
 };
 
-export type UserData = {
+export type VaultConfig = {
+  rewardIndex: number;
+  rewardDuration: anchor.BN | null;
+  stakeTokenCount: number | null;
+  maxStakePerUser: number | null;
+};
+
+export type ContributionData = {
   vault: PublicKey;
-  key: PublicKey;
+  rewardMint: PublicKey;
+  funder: PublicKey;
+  amount: anchor.BN;
+};
+
+export type MintWeightData = {
+  vault: PublicKey;
+  mint: PublicKey;
+  weight: number;
+};
+
+export type VaultWhitelistData = {
+  vault: PublicKey;
+  address: PublicKey;
+  whitelistType: WhitelistType;
+};
+
+export type UserRewardData = {
   rewardEarnedClaimed: anchor.BN;
   rewardEarnedPending: anchor.BN;
+  rewardPerTokenPaid: anchor.BN;
+};
+
+export type UserData = {
+  vault: PublicKey;
+  key: PublicKey;
+  rewards: UserRewardData[];
   mintStakedCount: number;
-  mintAccounts: PublicKey[];
+  stakedWeight: anchor.BN;
   lastStakeTime: anchor.BN;
+  rewardRecipient: PublicKey;
+  lockEndTime: anchor.BN;
+};
+
+export type StakeReceiptData = {
+  vault: PublicKey;
+  owner: PublicKey;
+  mint: PublicKey;
+  stakeAccount: PublicKey;
+  stakeTime: anchor.BN;
+  multiplier: number;
+  lockEndTime: anchor.BN;
+  amount: anchor.BN;
 };
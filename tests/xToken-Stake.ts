@@ -6,11 +6,15 @@ import { PublicKey, Keypair } from "@solana/web3.js";
 import {
   checkTokenAccounts,
   createVault,
+  getEscrowAddress,
   getRewardAddress,
+  getWeightAddress,
   getTokenAmounts,
   sleep,
+  spawnMoney,
 } from "./fixtures/lib";
-import { UserData, VaultData } from "./fixtures/vault";
+import { Mint } from "./fixtures/mint";
+import { UserData, Vault, VaultData, VaultRoleData } from "./fixtures/vault";
 
 describe("xToken-Stake", () => {
   // Configure the client to use the local cluster.
@@ -25,39 +29,153 @@ describe("xToken-Stake", () => {
     const vaultData = await vault.fetch();
 
     // check the result
-    expect(vaultData.rewardDuration.toNumber()).to.equal(128);
+    expect(vaultData.rewardStreams[0].rewardDuration.toNumber()).to.equal(
+      128
+    );
     expect(vaultData.stakeTokenCount).to.equal(500000);
-    expect(vaultData.rewardMintAccount.toString()).to.equal(
+    expect(vaultData.rewardStreams[0].rewardMintAccount.toString()).to.equal(
       vault.mintAccount.toString()
     );
-    expect(vaultData.funders.length).to.equal(5);
+    expect(vaultData.rewardStreamCount).to.equal(1);
     expect(vaultData.status.initialized !== null).to.be.true;
   });
 
-  xit("Authorize and Unauthorize Funder", async () => {
+  it("Grant and Revoke Roles", async () => {
     const { authority, vault } = await createVault(program);
 
-    // add funder
-    const { funderAdded } = await vault.addFunder(authority);
+    // grant the pauser role
+    const { holder: pauser, vaultRole } = await vault.grantRole(authority, {
+      pauser: {},
+    });
 
-    // fetch vault data
-    let vaultData = await vault.fetch();
+    const roleData = (await program.account.vaultRole.fetch(
+      vaultRole
+    )) as VaultRoleData;
+    expect(roleData.holder.toString()).to.equal(pauser.publicKey.toString());
+    expect(roleData.role.pauser !== undefined).to.be.true;
+    expect(roleData.rewardMint.toString()).to.equal(
+      PublicKey.default.toString()
+    );
+
+    // a pauser can pause with its role entry but not change settings
+    const pauserAccounts = await vault.roleAccounts(pauser.publicKey, {
+      pauser: {},
+    });
+    await vault.pause(pauser, pauserAccounts);
+    let failed = false;
+    try {
+      await vault.setCollection(pauser, PublicKey.default);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+    await vault.resume(authority);
+
+    // revoke the pauser role
+    await vault.revokeRole(authority, vaultRole);
+    expect(await program.provider.connection.getAccountInfo(vaultRole)).to.be
+      .null;
 
-    // check added funder
-    expect(vaultData.funders[0].toString()).to.equal(
-      funderAdded.publicKey.toString()
+    failed = false;
+    try {
+      await vault.pause(pauser, pauserAccounts);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+  });
+
+  it("Whitelist Mint", async () => {
+    const { authority, vault } = await createVault(program);
+
+    // whitelist a mint
+    const whitelistedMint = await Mint.create(program);
+    const { whitelist } = await vault.addWhitelist(
+      authority,
+      whitelistedMint.key,
+      { mint: {} }
     );
 
-    // remove funder
-    await vault.removeFunder(authority, funderAdded.publicKey);
+    let vaultData = await vault.fetch();
+    expect(vaultData.whitelistedMints).to.equal(1);
 
-    // fetch vault data
+    // staking a token which is not whitelisted fails
+    const { authority: userAuthority, user } = await vault.createUser();
+    let failed = false;
+    try {
+      await vault.stake(userAuthority, user, undefined, [
+        { pubkey: whitelist, isWritable: false, isSigner: false },
+      ]);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // staking the whitelisted mint succeeds
+    await vault.stake(userAuthority, user, whitelistedMint, [
+      { pubkey: whitelist, isWritable: false, isSigner: false },
+    ]);
+    const userData = await vault.fetchUser(user);
+    expect(userData.mintStakedCount).to.equal(1);
+
+    // remove whitelist
+    await vault.removeWhitelist(authority, whitelist);
     vaultData = await vault.fetch();
+    expect(vaultData.whitelistedMints).to.equal(0);
+  });
 
-    // check removed funder
-    expect(vaultData.funders[0].toString()).to.equal(
-      PublicKey.default.toString()
-    );
+  it("Pause and Resume", async () => {
+    const { authority, vault } = await createVault(program);
+
+    // stake before pausing
+    const { userAuthority, user, stakeAccount } = await vault.stake();
+
+    // pause
+    await vault.pause(authority);
+    let vaultData = await vault.fetch();
+    expect(vaultData.status.paused !== undefined).to.be.true;
+
+    // staking is blocked while paused
+    let failed = false;
+    try {
+      await vault.stake(userAuthority, user);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // unstaking keeps working while paused
+    await vault.unstake(userAuthority, user, stakeAccount);
+    const userData = await vault.fetchUser(user);
+    expect(userData.mintStakedCount).to.equal(0);
+
+    // resume
+    await vault.resume(authority);
+    vaultData = await vault.fetch();
+    expect(vaultData.status.initialized !== undefined).to.be.true;
+  });
+
+  it("Emergency Unstake", async () => {
+    const { authority, vault } = await createVault(program);
+    const { userAuthority, user, stakeAccount } = await vault.stake();
+
+    // emergency unstake works in any vault status
+    await vault.pause(authority);
+    await vault.emergencyUnstake(userAuthority, user, stakeAccount);
+
+    expect(
+      await checkTokenAccounts(
+        program,
+        userAuthority.publicKey,
+        stakeAccount.key
+      )
+    ).to.be.true;
+
+    const userData = await vault.fetchUser(user);
+    const vaultData = await vault.fetch();
+    expect(userData.mintStakedCount).to.equal(0);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(0);
+    expect(vaultData.stakedCount).to.equal(0);
   });
 
   xit("Fund Amount", async () => {
@@ -74,7 +192,6 @@ describe("xToken-Stake", () => {
 
     // fund
     await vault.fund({
-      authority,
       funder: funderAdded,
       funderAccount: funderTokenAccount.key,
       amount: new anchor.BN("1000000"),
@@ -82,12 +199,103 @@ describe("xToken-Stake", () => {
 
     let vaultData = await vault.fetch();
 
-    const rightSide = "1".padEnd(66, "0");
-    expect(vaultData.rewardRate.toString()).to.equal(
-      new anchor.BN(rightSide, 2).toString()
+    // reward rate is the whole vault emission per unit of emission weight, scaled by 2^64,
+    // a constant curve weighs 10000 every second
+    expect(vaultData.rewardStreams[0].rewardRate.toString()).to.equal(
+      amount.shln(64).divn(10000 * vault.rewardDuration).toString()
     );
   });
 
+  it("Withdraw Unallocated", async () => {
+    const { mint, authority, vault } = await createVault(program);
+
+    // two funders contribute 3:1
+    const { funderAdded } = await vault.addFunder(authority);
+    const { funderAdded: partner } = await vault.addFunder(authority);
+    const funderTokenAccount = await mint.createAssociatedAccount(
+      funderAdded.publicKey
+    );
+    const partnerTokenAccount = await mint.createAssociatedAccount(
+      partner.publicKey
+    );
+    await mint.mintTokens(funderTokenAccount, 3000);
+    await mint.mintTokens(partnerTokenAccount, 1000);
+    await vault.fund({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+      amount: new anchor.BN(3000),
+    });
+    await vault.fund({
+      funder: partner,
+      funderAccount: partnerTokenAccount.key,
+      amount: new anchor.BN(1000),
+    });
+
+    let vaultData = await vault.fetch();
+    expect(vaultData.rewardStreams[0].totalContributed.toNumber()).to.equal(
+      4000
+    );
+
+    // refunds wait for the funded period to end, nothing was staked so nothing is owed
+    await sleep(2000);
+    await vault.withdrawUnallocated({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+    });
+
+    vaultData = await vault.fetch();
+    expect(vaultData.rewardStreams[0].refunding).to.be.true;
+    expect(vaultData.rewardStreams[0].contributionCount).to.equal(1);
+
+    // a refunding stream can not be funded again
+    let failed = false;
+    try {
+      await vault.fund({
+        funder: funderAdded,
+        funderAccount: funderTokenAccount.key,
+        amount: new anchor.BN(1000),
+      });
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // anyone can settle the partner contribution, the refund goes to the partner
+    await vault.withdrawUnallocated({
+      funder: partner,
+      funderAccount: partnerTokenAccount.key,
+      caller: authority,
+    });
+
+    vaultData = await vault.fetch();
+    expect(vaultData.rewardStreams[0].refunding).to.be.false;
+    expect(vaultData.rewardStreams[0].contributionCount).to.equal(0);
+    expect(vaultData.rewardStreams[0].totalContributed.toNumber()).to.equal(0);
+    expect(
+      await getTokenAmounts(
+        program,
+        funderAdded.publicKey,
+        funderTokenAccount.key
+      )
+    ).to.equal(3000);
+    expect(
+      await getTokenAmounts(program, partner.publicKey, partnerTokenAccount.key)
+    ).to.equal(1000);
+
+    // once every contribution is settled the stream can be funded again
+    await vault.fund({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+      amount: new anchor.BN(1000),
+    });
+
+    vaultData = await vault.fetch();
+    expect(vaultData.rewardStreams[0].totalContributed.toNumber()).to.equal(
+      1000
+    );
+    expect(vaultData.rewardStreams[0].contributionCount).to.equal(1);
+  });
+
   xit("Create User", async () => {
     const { vault } = await createVault(program);
 
@@ -99,12 +307,12 @@ describe("xToken-Stake", () => {
 
     expect(vaultData.userCount).to.equal(1);
     expect(userData.vault.toString()).to.equal(vault.key.toString());
-    expect(userData.mintAccounts.length).to.equal(0);
+    expect(userData.mintStakedCount).to.equal(0);
     expect(userData.key.toString()).to.equal(
       userAuthority.publicKey.toString()
     );
-    expect(userData.rewardEarnedClaimed.toNumber()).to.equal(0);
-    expect(userData.rewardEarnedPending.toNumber()).to.equal(0);
+    expect(userData.rewards[0].rewardEarnedClaimed.toNumber()).to.equal(0);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(0);
   });
 
   xit("Stake and Unstake", async () => {
@@ -125,7 +333,6 @@ describe("xToken-Stake", () => {
 
     // fund
     await vault.fund({
-      authority,
       funder: funderAdded,
       funderAccount: funderTokenAccount.key,
       amount: new anchor.BN("1000000"),
@@ -148,9 +355,12 @@ describe("xToken-Stake", () => {
 
     // check user data and vault data
     expect(userData.mintStakedCount).to.equal(1);
-    expect(userData.mintAccounts.length).to.equal(1);
-    expect(userData.mintAccounts[0].toString()).to.equal(
-      stakeAccount.key.toString()
+    const receiptData = await vault.fetchReceipt(stakeAccount.key);
+    expect(receiptData.owner.toString()).to.equal(
+      userAuthority.publicKey.toString()
+    );
+    expect(receiptData.mint.toString()).to.equal(
+      stakeAccount.mint.key.toString()
     );
     expect(vaultData.stakedCount).to.equal(1);
 
@@ -172,9 +382,9 @@ describe("xToken-Stake", () => {
     vaultData = await vault.fetch();
 
     expect(userData.mintStakedCount).to.equal(0);
-    expect(userData.mintAccounts.length).to.equal(0);
+    expect(await vault.fetchReceipt(stakeAccount.key)).to.be.null;
     expect(vaultData.stakedCount).to.equal(0);
-    const firstEarned = userData.rewardEarnedPending.toNumber();
+    const firstEarned = userData.rewards[0].rewardEarnedPending.toNumber();
 
     //------------- stake again after 5 seconds ------------//
     await sleep(5000);
@@ -189,12 +399,524 @@ describe("xToken-Stake", () => {
     vaultData = await vault.fetch();
 
     expect(userData.mintStakedCount).to.equal(1);
-    expect(userData.mintAccounts.length).to.equal(1);
-    expect(userData.mintAccounts[0].toString()).to.equal(
-      secondStakeAccount.key.toString()
-    );
+    expect(await vault.fetchReceipt(secondStakeAccount.key)).to.not.be.null;
     expect(vaultData.stakedCount).to.equal(1);
-    expect(userData.rewardEarnedPending.toNumber()).to.equal(firstEarned);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(firstEarned);
+  });
+
+  it("Stake and Unstake Many", async () => {
+    const { vault } = await createVault(program);
+    const { authority: userAuthority, user } = await vault.createUser();
+
+    // stake several tokens in one instruction
+    const { stakeAccounts } = await vault.stakeMany(userAuthority, user, 3);
+
+    let userData = await vault.fetchUser(user);
+    let vaultData = await vault.fetch();
+    expect(userData.mintStakedCount).to.equal(3);
+    expect(vaultData.stakedCount).to.equal(3);
+
+    // unstake all of them in one instruction
+    await vault.unstakeMany(userAuthority, user, stakeAccounts);
+
+    userData = await vault.fetchUser(user);
+    vaultData = await vault.fetch();
+    expect(userData.mintStakedCount).to.equal(0);
+    expect(vaultData.stakedCount).to.equal(0);
+    for (const stakeAccount of stakeAccounts) {
+      expect(
+        await checkTokenAccounts(
+          program,
+          userAuthority.publicKey,
+          stakeAccount.key
+        )
+      ).to.be.true;
+    }
+  });
+
+  it("Lock Tiers", async () => {
+    const { authority, vault } = await createVault(program);
+
+    // a single 100 seconds tier doubling rewards, early unstakes rejected
+    await vault.setLockTiers(
+      authority,
+      [{ duration: new anchor.BN(100), multiplier: 20000 }],
+      0
+    );
+
+    let vaultData = await vault.fetch();
+    expect(vaultData.lockTierCount).to.equal(1);
+
+    const { authority: userAuthority, user } = await vault.createUser();
+    const { stakeAccount } = await vault.stake(
+      userAuthority,
+      user,
+      undefined,
+      [],
+      0
+    );
+
+    const receiptData = await vault.fetchReceipt(stakeAccount.key);
+    expect(receiptData.multiplier).to.equal(20000);
+    expect(receiptData.lockEndTime.gt(receiptData.stakeTime)).to.be.true;
+
+    let userData = await vault.fetchUser(user);
+    expect(userData.stakedWeight.toNumber()).to.equal(20000);
+    expect(userData.lockEndTime.toString()).to.equal(
+      receiptData.lockEndTime.toString()
+    );
+
+    // unstaking before the lock ends fails
+    let failed = false;
+    try {
+      await vault.unstake(userAuthority, user, stakeAccount);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // rewards can not be claimed ahead of an early unstake to dodge its penalty
+    failed = false;
+    try {
+      await vault.claim(authority.publicKey, userAuthority, user);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // nor can the token leave through an emergency unstake
+    failed = false;
+    try {
+      await vault.emergencyUnstake(userAuthority, user, stakeAccount);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // with a penalty the early unstake goes through
+    await vault.setLockTiers(
+      authority,
+      [{ duration: new anchor.BN(100), multiplier: 20000 }],
+      5000
+    );
+    await vault.unstake(userAuthority, user, stakeAccount);
+
+    userData = await vault.fetchUser(user);
+    vaultData = await vault.fetch();
+    expect(userData.stakedWeight.toNumber()).to.equal(0);
+    expect(userData.lockEndTime.toNumber()).to.equal(0);
+    expect(vaultData.stakedWeight.toNumber()).to.equal(0);
+  });
+
+  it("Mint Weights", async () => {
+    const { authority, vault } = await createVault(program);
+
+    // a rare mint weighs three times a common one
+    const rareMint = await Mint.create(program);
+    await vault.setMintWeight(authority, rareMint.key, 30000);
+
+    let vaultData = await vault.fetch();
+    expect(vaultData.weightedMints).to.equal(1);
+
+    const { authority: userAuthority, user } = await vault.createUser();
+    const [rareWeight, _] = await getWeightAddress(
+      vault.key,
+      rareMint.key,
+      program
+    );
+    const { stakeAccount: rareAccount } = await vault.stake(
+      userAuthority,
+      user,
+      rareMint,
+      [{ pubkey: rareWeight, isWritable: false, isSigner: false }]
+    );
+    expect((await vault.fetchReceipt(rareAccount.key)).multiplier).to.equal(
+      30000
+    );
+
+    // a mint without weight passes its empty weight account and weighs the base
+    const commonMint = await Mint.create(program);
+    const [commonWeight] = await getWeightAddress(
+      vault.key,
+      commonMint.key,
+      program
+    );
+    const { stakeAccount: commonAccount } = await vault.stake(
+      userAuthority,
+      user,
+      commonMint,
+      [{ pubkey: commonWeight, isWritable: false, isSigner: false }]
+    );
+    expect((await vault.fetchReceipt(commonAccount.key)).multiplier).to.equal(
+      10000
+    );
+
+    const userData = await vault.fetchUser(user);
+    vaultData = await vault.fetch();
+    expect(userData.stakedWeight.toNumber()).to.equal(40000);
+    expect(vaultData.stakedWeight.toNumber()).to.equal(40000);
+    expect(vaultData.stakedCount).to.equal(2);
+  });
+
+  it("Transfer Authority", async () => {
+    const { authority, vault } = await createVault(program);
+    const newAuthority = Keypair.generate();
+
+    await vault.proposeAuthority(authority, newAuthority.publicKey);
+    let vaultData = await vault.fetch();
+    expect(vaultData.authority.toString()).to.equal(
+      authority.publicKey.toString()
+    );
+    expect(vaultData.pendingAuthority.toString()).to.equal(
+      newAuthority.publicKey.toString()
+    );
+
+    // only the proposed key can accept
+    let failed = false;
+    try {
+      await vault.acceptAuthority(Keypair.generate());
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    await vault.acceptAuthority(newAuthority);
+    vaultData = await vault.fetch();
+    expect(vaultData.authority.toString()).to.equal(
+      newAuthority.publicKey.toString()
+    );
+    expect(vaultData.pendingAuthority.toString()).to.equal(
+      PublicKey.default.toString()
+    );
+
+    // the old authority lost control, the new one has it
+    failed = false;
+    try {
+      await vault.pause(authority);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+    await vault.pause(newAuthority);
+  });
+
+  it("Update Vault Config", async () => {
+    const { authority, vault } = await createVault(program);
+    const { authority: userAuthority, user } = await vault.createUser();
+    await vault.stake(userAuthority, user);
+
+    await vault.updateConfig(authority, {
+      rewardIndex: 0,
+      rewardDuration: new anchor.BN(100),
+      stakeTokenCount: 10,
+      maxStakePerUser: 1,
+    });
+
+    const vaultData = await vault.fetch();
+    expect(vaultData.rewardStreams[0].rewardDuration.toNumber()).to.equal(100);
+    expect(vaultData.stakeTokenCount).to.equal(10);
+    expect(vaultData.maxStakePerUser).to.equal(1);
+
+    // the per user cap is reached
+    let failed = false;
+    try {
+      await vault.stake(userAuthority, user);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // the stake token count can not shrink below the staked count
+    failed = false;
+    try {
+      await vault.updateConfig(authority, {
+        rewardIndex: 0,
+        rewardDuration: null,
+        stakeTokenCount: 0,
+        maxStakePerUser: null,
+      });
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+  });
+
+  it("Emission Schedules", async () => {
+    const { mint, authority, vault } = await createVault(program);
+    const emptyCheckpoints = Array(4).fill({
+      offset: new anchor.BN(0),
+      rate: 0,
+    });
+
+    // unordered checkpoints are rejected
+    let failed = false;
+    try {
+      await vault.setEmissionSchedule(authority, {
+        curve: { piecewise: {} },
+        param: new anchor.BN(0),
+        checkpointCount: 2,
+        checkpoints: [
+          { offset: new anchor.BN(10), rate: 10000 },
+          { offset: new anchor.BN(5), rate: 5000 },
+          ...emptyCheckpoints.slice(2),
+        ],
+      });
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // the rate decays linearly to zero over the funded period
+    await vault.setEmissionSchedule(authority, {
+      curve: { linearDecay: {} },
+      param: new anchor.BN(0),
+      checkpointCount: 0,
+      checkpoints: emptyCheckpoints,
+    });
+
+    const { funderAdded } = await vault.addFunder(authority);
+    const funderTokenAccount = await mint.createAssociatedAccount(
+      funderAdded.publicKey
+    );
+    const amount = new anchor.BN(1000000);
+    await mint.mintTokens(funderTokenAccount, amount.toNumber());
+    await vault.fund({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+      amount,
+    });
+
+    // the full rate starts twice as high as a constant rate emitting the same amount
+    const vaultData = await vault.fetch();
+    const stream = vaultData.rewardStreams[0];
+    expect(stream.schedule.linearDecay).to.not.be.undefined;
+    expect(
+      stream.rewardDurationDeadline.sub(stream.periodStart).toNumber()
+    ).to.equal(vault.rewardDuration);
+    const periodWeight =
+      10000 * vault.rewardDuration -
+      Math.floor((10000 * vault.rewardDuration) / 2);
+    expect(stream.rewardRate.toString()).to.equal(
+      amount.shln(64).divn(periodWeight).toString()
+    );
+
+    // the curve of a running period can not change
+    failed = false;
+    try {
+      await vault.setEmissionSchedule(authority, {
+        curve: { constant: {} },
+        param: new anchor.BN(0),
+        checkpointCount: 0,
+        checkpoints: emptyCheckpoints,
+      });
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+  });
+
+  it("Collection", async () => {
+    const { authority, vault } = await createVault(program);
+    const { authority: userAuthority, user } = await vault.createUser();
+
+    const collection = Keypair.generate().publicKey;
+    await vault.setCollection(authority, collection);
+
+    let vaultData = await vault.fetch();
+    expect(vaultData.collection.toString()).to.equal(collection.toString());
+
+    // staking without the mint and metadata proving membership fails
+    let failed = false;
+    try {
+      await vault.stake(userAuthority, user);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    // the default key accepts tokens of any collection again
+    await vault.setCollection(authority, PublicKey.default);
+    const { stakeAccount } = await vault.stake(userAuthority, user);
+    expect(await vault.fetchReceipt(stakeAccount.key)).to.not.be.null;
+  });
+
+  it("Fungible Deposit and Withdraw", async () => {
+    const stakeMint = await Mint.create(program);
+    const { vault } = await createVault(program, stakeMint);
+
+    let vaultData = await vault.fetch();
+    expect(vaultData.stakeMode.fungible !== undefined).to.be.true;
+    expect(vaultData.stakeMint.toString()).to.equal(stakeMint.key.toString());
+
+    const { authority: userAuthority, user } = await vault.createUser();
+    const stakerAccount = await stakeMint.createAssociatedAccount(
+      userAuthority.publicKey
+    );
+    await stakeMint.mintTokens(stakerAccount, 1000);
+
+    // deposited amount is the staked weight
+    const { escrowAccount } = await vault.deposit(
+      userAuthority,
+      user,
+      stakeMint,
+      stakerAccount.key,
+      new anchor.BN(1000)
+    );
+
+    let userData = await vault.fetchUser(user);
+    vaultData = await vault.fetch();
+    expect(userData.stakedWeight.toNumber()).to.equal(1000);
+    expect(vaultData.stakedWeight.toNumber()).to.equal(1000);
+    expect(userData.mintStakedCount).to.equal(0);
+
+    // partial withdraw
+    await vault.withdraw(
+      userAuthority,
+      user,
+      stakeMint,
+      stakerAccount.key,
+      new anchor.BN(400)
+    );
+
+    userData = await vault.fetchUser(user);
+    vaultData = await vault.fetch();
+    expect(userData.stakedWeight.toNumber()).to.equal(600);
+    expect(vaultData.stakedWeight.toNumber()).to.equal(600);
+
+    const [escrow, _] = await getEscrowAddress(vault.key, program);
+    expect(await getTokenAmounts(program, escrow, escrowAccount)).to.equal(600);
+
+    // nft staking is rejected by a fungible vault
+    let failed = false;
+    try {
+      await vault.stake(userAuthority, user);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+  });
+
+  it("Compound", async () => {
+    // the vault pays its rewards in the stake mint
+    const stakeMint = await Mint.create(program);
+    const { authority, vault } = await Vault.create({
+      program,
+      mint: stakeMint,
+      duration: 2,
+      stakeTokenCount: 500000,
+      stakeMint,
+    });
+
+    const { authority: userAuthority, user } = await vault.createUser();
+    const stakerAccount = await stakeMint.createAssociatedAccount(
+      userAuthority.publicKey
+    );
+    await stakeMint.mintTokens(stakerAccount, 1000);
+    await vault.deposit(
+      userAuthority,
+      user,
+      stakeMint,
+      stakerAccount.key,
+      new anchor.BN(1000)
+    );
+
+    const { funderAdded } = await vault.addFunder(authority);
+    const funderTokenAccount = await stakeMint.createAssociatedAccount(
+      funderAdded.publicKey
+    );
+    await stakeMint.mintTokens(funderTokenAccount, 1000);
+    await vault.fund({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+      amount: new anchor.BN(1000),
+    });
+
+    // the whole period is emitted to the only staker, then restaked
+    await sleep(3000);
+    await vault.compound(userAuthority, user, stakeMint);
+
+    const userData = await vault.fetchUser(user);
+    const vaultData = await vault.fetch();
+    const compounded = userData.rewards[0].rewardEarnedClaimed.toNumber();
+    expect(compounded).to.be.greaterThan(0);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(0);
+    expect(userData.stakedWeight.toNumber()).to.equal(1000 + compounded);
+    expect(vaultData.stakedWeight.toNumber()).to.equal(1000 + compounded);
+
+    // nothing is left to compound
+    let failed = false;
+    try {
+      await vault.compound(userAuthority, user, stakeMint);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+  });
+
+  it("Escrow Custody", async () => {
+    const { vault } = await createVault(program, undefined, { escrow: {} });
+
+    const vaultData = await vault.fetch();
+    expect(vaultData.custodyMode.escrow !== undefined).to.be.true;
+
+    // the staked token moves into the escrow of the vault pda
+    const { userAuthority, user, stakeAccount, stakeMint } =
+      await vault.stake();
+    const { vaultPda, escrowAccount } = await vault.getStakeEscrow(
+      userAuthority.publicKey,
+      stakeMint
+    );
+    expect(
+      await getTokenAmounts(program, userAuthority.publicKey, stakeAccount.key)
+    ).to.equal(0);
+    expect(await getTokenAmounts(program, vaultPda, escrowAccount)).to.equal(1);
+
+    // the receipt records its part of the escrow, only that part is returned
+    const receiptData = await vault.fetchReceipt(stakeAccount.key);
+    expect(receiptData.amount.toNumber()).to.equal(1);
+
+    // unstake moves it back and closes the emptied escrow
+    await vault.unstake(userAuthority, user, stakeAccount);
+
+    const userData = await vault.fetchUser(user);
+    expect(userData.mintStakedCount).to.equal(0);
+    expect(
+      await getTokenAmounts(program, userAuthority.publicKey, stakeAccount.key)
+    ).to.equal(1);
+    expect(await program.provider.connection.getAccountInfo(escrowAccount)).to
+      .be.null;
+  });
+
+  it("Freeze Custody", async () => {
+    const { vault } = await createVault(program, undefined, { freeze: {} });
+
+    // the staked token stays in the wallet, delegated to the vault pda and frozen
+    const { userAuthority, user, stakeAccount, stakeMint } =
+      await vault.stake();
+    const { vaultPda } = await vault.getStakeEscrow(
+      userAuthority.publicKey,
+      stakeMint
+    );
+    let info = (
+      (await program.provider.connection.getParsedAccountInfo(stakeAccount.key))
+        .value.data as anchor.web3.ParsedAccountData
+    ).parsed.info;
+    expect(info.owner).to.equal(userAuthority.publicKey.toString());
+    expect(info.delegate).to.equal(vaultPda.toString());
+    expect(info.state).to.equal("frozen");
+
+    // unstake thaws the token and revokes the delegation
+    await vault.unstake(userAuthority, user, stakeAccount);
+
+    info = (
+      (await program.provider.connection.getParsedAccountInfo(stakeAccount.key))
+        .value.data as anchor.web3.ParsedAccountData
+    ).parsed.info;
+    expect(info.delegate).to.be.undefined;
+    expect(info.state).to.equal("initialized");
+    expect(
+      await getTokenAmounts(program, userAuthority.publicKey, stakeAccount.key)
+    ).to.equal(1);
   });
 
   xit("claim", async () => {
@@ -203,6 +925,10 @@ describe("xToken-Stake", () => {
     //-----------    create vault     ------------//
     const { mint, authority, vault } = await createVault(program);
 
+    //----------- create user and stake ------------//
+    // stake before funding, rewards only accrue until the funded deadline
+    const { userAuthority, user } = await vault.stake();
+
     //----------- add funder and fund ------------//
     const { funderAdded } = await vault.addFunder(authority);
     const funderTokenAccount = await mint.createAssociatedAccount(
@@ -214,25 +940,162 @@ describe("xToken-Stake", () => {
 
     // fund
     await vault.fund({
-      authority,
       funder: funderAdded,
       funderAccount: funderTokenAccount.key,
       amount: new anchor.BN("1000000"),
     });
 
-    //----------- create user and stake ------------//
-    const { userAuthority, user, stakeAccount } = await vault.stake();
-
     //----------- claim after 5 seconds ------------//
     await sleep(5000);
     await vault.claim(authority.publicKey, userAuthority, user);
 
     userData = await vault.fetchUser(user);
-    expect(userData.rewardEarnedPending.toNumber()).to.equal(0);
-    expect(userData.rewardEarnedClaimed.toNumber()).to.above(0);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(0);
+    expect(userData.rewards[0].rewardEarnedClaimed.toNumber()).to.above(0);
+  });
+
+  it("Crank Claim", async () => {
+    const { mint, authority, vault } = await createVault(program);
+    const { authority: userAuthority, user } = await vault.createUser();
+    await vault.stake(userAuthority, user);
+
+    const { funderAdded } = await vault.addFunder(authority);
+    const funderTokenAccount = await mint.createAssociatedAccount(
+      funderAdded.publicKey
+    );
+    await mint.mintTokens(funderTokenAccount, 1000);
+    await vault.fund({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+      amount: new anchor.BN(1000),
+    });
+
+    // nothing can be pushed before the user sets a recipient
+    const caller = Keypair.generate();
+    await spawnMoney(program, caller.publicKey, 1);
+    const recipient = Keypair.generate().publicKey;
+    let failed = false;
+    try {
+      await vault.crankClaim(caller, user, recipient);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    await vault.setRewardRecipient(userAuthority, user, recipient);
+    let userData = await vault.fetchUser(user);
+    expect(userData.rewardRecipient.toString()).to.equal(recipient.toString());
+
+    // anyone pushes the rewards, the caller pays the recipient account rent
+    await sleep(2000);
+    await vault.crankClaim(caller, user, recipient);
+
+    userData = await vault.fetchUser(user);
+    const claimed = userData.rewards[0].rewardEarnedClaimed.toNumber();
+    expect(claimed).to.be.above(0);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(0);
+    const recipientAccount = await mint.getAssociatedTokenAddress(recipient);
+    expect(
+      await getTokenAmounts(program, recipient, recipientAccount)
+    ).to.equal(claimed);
+
+    // only the recipient set by the user is paid
+    failed = false;
+    try {
+      await vault.crankClaim(caller, user, Keypair.generate().publicKey);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
   });
 
-  xit("close user", async () => {
+  it("Multiple Reward Streams", async () => {
+    const { mint, authority, vault } = await createVault(program);
+
+    // a partner adds a second reward stream with its own mint
+    const partnerMint = await Mint.create(program);
+    const { mintAccount: partnerMintAccount } = await vault.addRewardStream(
+      authority,
+      partnerMint,
+      vault.rewardDuration
+    );
+
+    let vaultData = await vault.fetch();
+    expect(vaultData.rewardStreamCount).to.equal(2);
+    expect(vaultData.rewardStreams[1].rewardMint.toString()).to.equal(
+      partnerMint.key.toString()
+    );
+
+    // stake before funding, rewards only accrue until the funded deadline
+    const { userAuthority, user } = await vault.stake();
+
+    // each funder role is scoped to one stream
+    const { funderAdded } = await vault.addFunder(authority);
+    const { funderAdded: partner } = await vault.addFunder(
+      authority,
+      undefined,
+      partnerMint.key
+    );
+
+    const amount = new anchor.BN("1000000");
+    const funderTokenAccount = await mint.createAssociatedAccount(
+      funderAdded.publicKey
+    );
+    await mint.mintTokens(funderTokenAccount, amount.toNumber());
+    await vault.fund({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+      amount,
+    });
+
+    const partnerTokenAccount = await partnerMint.createAssociatedAccount(
+      partner.publicKey
+    );
+    await partnerMint.mintTokens(partnerTokenAccount, amount.toNumber());
+
+    // a funder of the first stream can not fund the partner stream
+    const strayTokenAccount = await partnerMint.createAssociatedAccount(
+      funderAdded.publicKey
+    );
+    await partnerMint.mintTokens(strayTokenAccount, amount.toNumber());
+    let failed = false;
+    try {
+      await vault.fund({
+        funder: funderAdded,
+        funderAccount: strayTokenAccount.key,
+        amount,
+        rewardAccount: partnerMintAccount,
+      });
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    await vault.fund({
+      funder: partner,
+      funderAccount: partnerTokenAccount.key,
+      amount,
+      rewardAccount: partnerMintAccount,
+    });
+
+    // claim only the partner stream
+    await sleep(5000);
+    await vault.claim(authority.publicKey, userAuthority, user, 1);
+
+    let userData = await vault.fetchUser(user);
+    expect(userData.rewards[0].rewardEarnedClaimed.toNumber()).to.equal(0);
+    expect(userData.rewards[1].rewardEarnedClaimed.toNumber()).to.above(0);
+
+    // claim every stream
+    await vault.claim(authority.publicKey, userAuthority, user);
+
+    userData = await vault.fetchUser(user);
+    expect(userData.rewards[0].rewardEarnedClaimed.toNumber()).to.above(0);
+    expect(userData.rewards[0].rewardEarnedPending.toNumber()).to.equal(0);
+    expect(userData.rewards[1].rewardEarnedPending.toNumber()).to.equal(0);
+  });
+
+  it("close user", async () => {
     const { vault } = await createVault(program);
 
     // create user
@@ -241,14 +1104,26 @@ describe("xToken-Stake", () => {
     // close user
     await vault.closeUser(userAuthority, user);
 
-    const vaultData = await vault.fetch();
+    let vaultData = await vault.fetch();
     expect(vaultData.userCount).to.equal(0);
+    expect(await vault.fetchUser(user)).to.be.null;
+
+    // the same authority can register again
+    const { user: recreated } = await vault.createUser(userAuthority);
+    expect(recreated.toString()).to.equal(user.toString());
+
+    vaultData = await vault.fetch();
+    expect(vaultData.userCount).to.equal(1);
   });
 
   it("close program", async () => {
     //-----------     create vault    ------------//
     const { authority, vault, mint } = await createVault(program);
 
+    // a partner stream that is never funded does not keep the vault open
+    const partnerMint = await Mint.create(program);
+    await vault.addRewardStream(authority, partnerMint, vault.rewardDuration);
+
     //----------- add funder and fund ------------//
     const { funderAdded } = await vault.addFunder(authority);
     const funderTokenAccount = await mint.createAssociatedAccount(
@@ -260,7 +1135,6 @@ describe("xToken-Stake", () => {
 
     // fund
     await vault.fund({
-      authority,
       funder: funderAdded,
       funderAccount: funderTokenAccount.key,
       amount: new anchor.BN("1000000"),
@@ -271,8 +1145,23 @@ describe("xToken-Stake", () => {
       refundee.publicKey
     );
 
+    // the vault can not be closed before the contribution is settled
+    await sleep(2000);
+    let failed = false;
+    try {
+      await vault.close(authority, refundee);
+    } catch (e) {
+      failed = true;
+    }
+    expect(failed).to.be.true;
+
+    await vault.withdrawUnallocated({
+      funder: funderAdded,
+      funderAccount: funderTokenAccount.key,
+    });
+
     // close program
-    await vault.close(authority, refundee, refundeeAccount);
+    await vault.close(authority, refundee);
 
     const [reward, _] = await getRewardAddress(vault.key, program);
 
@@ -284,7 +1173,14 @@ describe("xToken-Stake", () => {
 
     expect(!rewardTokenAccounts).to.be.true;
     expect(
-      await getTokenAmounts(program, refundee.publicKey, refundeeAccount)
+      await getTokenAmounts(
+        program,
+        funderAdded.publicKey,
+        funderTokenAccount.key
+      )
     ).to.equal(1000000);
+    expect(
+      await getTokenAmounts(program, refundee.publicKey, refundeeAccount)
+    ).to.equal(0);
   });
 });